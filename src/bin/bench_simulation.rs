@@ -0,0 +1,112 @@
+//! Times the packed bitplane backend against stepping one cell at a time.
+//!
+//! Run `cargo run --release --bin bench_simulation`. Every case steps the same
+//! random board through both paths, checks they agree and prints how many
+//! generations each manages per second.
+
+use std::time::{Duration, Instant};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use shift_o::game::{
+    rulestring::Rulestring,
+    simulation::{
+        Boundary, Count, Grid, Layout, Radius, Rule, RuleSet, Schedule, Tile, Topology, step_cells,
+        step_phase,
+    },
+};
+
+const SIZE: usize = 256;
+/// How long each path of each case gets.
+const BUDGET: Duration = Duration::from_secs(2);
+/// Generations both paths have to agree on before they are timed.
+const CHECKED: usize = 20;
+
+fn main() {
+    let board = Layout::new(SIZE, SIZE);
+    let cases = [
+        (
+            "Life on a torus",
+            board.with_boundary(Boundary::Wrap),
+            life("B3/S23"),
+        ),
+        (
+            "Day & Night, mirrored",
+            board.with_boundary(Boundary::Mirror),
+            life("B3678/S34678"),
+        ),
+        (
+            "Radius two majority",
+            board.with_radius(Radius::Two),
+            majority(13),
+        ),
+        (
+            "Hex checkerboard",
+            board
+                .with_topology(Topology::Hex)
+                .with_schedule(Schedule::Checkerboard),
+            majority(4),
+        ),
+    ];
+    let mut rng = StdRng::seed_from_u64(0);
+    for (name, layout, rules) in cases {
+        let grid: Grid = (0..layout.len())
+            .map(|_| if rng.gen_bool(0.3) { Tile::Yellow } else { Tile::Blue } as u8)
+            .collect();
+        let (mut cells, mut packed) = (grid.clone(), grid.clone());
+        for _ in 0..CHECKED {
+            cells = generation(&cells, layout, &rules, step_cells);
+            packed = generation(&packed, layout, &rules, step_phase);
+            assert_eq!(cells, packed, "{name}: the paths disagree");
+        }
+        let cells = per_second(&grid, layout, &rules, step_cells);
+        let packed = per_second(&grid, layout, &rules, step_phase);
+        println!(
+            "{name}, {SIZE}x{SIZE}: {cells:.0} steps/s cell by cell, {packed:.0} packed ({:.0}x)",
+            packed / cells
+        );
+    }
+}
+
+type Stepper = fn(&[u8], Layout, &RuleSet, usize, &mut StdRng) -> Grid;
+
+fn life(rulestring: &str) -> RuleSet {
+    let rulestring: Rulestring = rulestring.parse().unwrap();
+    rulestring.rules(Tile::Blue, Tile::Yellow)
+}
+
+/// Each color turns into the other once `threshold` of its neighbours have.
+fn majority(threshold: u8) -> RuleSet {
+    let flip = |from, to| {
+        let rule = Rule {
+            tiles: [Some(to), None],
+            count: Count::at_least(threshold),
+            result: Some(to),
+            ..Rule::default()
+        };
+        (from, vec![rule])
+    };
+    RuleSet::from([
+        flip(Tile::Blue, Tile::Yellow),
+        flip(Tile::Yellow, Tile::Blue),
+    ])
+}
+
+fn generation(grid: &[u8], layout: Layout, rules: &RuleSet, step: Stepper) -> Grid {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut grid = grid.to_vec();
+    for phase in 0..layout.schedule.phases(layout) {
+        grid = step(&grid, layout, rules, phase, &mut rng);
+    }
+    grid
+}
+
+fn per_second(grid: &[u8], layout: Layout, rules: &RuleSet, step: Stepper) -> f64 {
+    let start = Instant::now();
+    let mut grid = grid.to_vec();
+    let mut steps = 0;
+    while start.elapsed() < BUDGET {
+        grid = generation(&grid, layout, rules, step);
+        steps += 1;
+    }
+    steps as f64 / start.elapsed().as_secs_f64()
+}
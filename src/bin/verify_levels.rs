@@ -0,0 +1,419 @@
+//! Checks level packs for problems the game would only run into at runtime.
+//!
+//! Run `cargo run --bin verify_levels` from the repository root after editing
+//! `assets/levels`, or pass the paths of other packs to check those instead.
+//! Every problem found is printed and the exit code is non-zero.
+//!
+//! With `--solve` every level is also run through the solver, which prints the
+//! fewest steps it found each one in and fails on levels it proves unsolvable.
+
+use std::{collections::HashMap, env, fs, process::ExitCode};
+
+use shift_o::game::{
+    level::{Board, LevelData, MAX_GRID_SIZE},
+    logic::MAX_GENERATIONS,
+    objective::Objective,
+    pack::LevelPack,
+    simulation::{
+        ANY, Boundary, HOLE, MAX_NEIGHBOURS, Radius, Rule, Tile, Topology, WALL, color_pool,
+        is_fixed,
+    },
+    solver::{Verdict, solve},
+};
+
+const PACKS: &str = "assets/levels";
+/// Boards the solver may visit per level and pass before leaving it undecided.
+const SOLVER_BUDGET: usize = 5_000_000;
+
+fn main() -> ExitCode {
+    let mut errors = vec![];
+    let (flags, mut paths): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let solving = flags.iter().any(|flag| flag == "--solve");
+    for flag in flags.iter().filter(|flag| *flag != "--solve") {
+        errors.push(format!("unknown flag {flag}, only --solve is understood"));
+    }
+    if paths.is_empty() {
+        match fs::read_dir(PACKS) {
+            Ok(entries) => {
+                paths = entries
+                    .filter_map(|entry| Some(entry.ok()?.path()))
+                    .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+                    .map(|path| path.display().to_string())
+                    .collect()
+            }
+            Err(error) => errors.push(format!("{PACKS}: {error}")),
+        }
+        paths.sort();
+    }
+
+    // Progress is kept by pack title, so two packs sharing one would share it.
+    let mut titles = HashMap::new();
+    let mut levels = 0;
+    for path in &paths {
+        let Some(pack) = load(path, &mut errors) else {
+            continue;
+        };
+        if pack.title.trim().is_empty() {
+            errors.push(format!("{path}: pack has no title"));
+        } else if let Some(other) = titles.insert(pack.title.clone(), path) {
+            errors.push(format!("{path}: {other} is also titled {:?}", pack.title));
+        }
+        if pack.author.trim().is_empty() {
+            errors.push(format!("{path}: pack has no author"));
+        }
+        if pack.levels.is_empty() {
+            errors.push(format!("{path}: has no levels to play"));
+        }
+        for (index, level) in pack.levels.iter().enumerate() {
+            let mut problems = check_level(level);
+            // Levels that don't load right can't be searched either.
+            if solving && problems.is_empty() {
+                problems.extend(solve_level(level, &format!("{path}, level {index}")));
+            }
+            for error in problems {
+                errors.push(format!("{path}, level {index} ({}): {error}", level.name));
+            }
+        }
+        levels += pack.levels.len();
+    }
+
+    if errors.is_empty() {
+        println!("{} packs with {levels} levels ok", paths.len());
+        return ExitCode::SUCCESS;
+    }
+    for error in &errors {
+        eprintln!("error: {error}");
+    }
+    ExitCode::FAILURE
+}
+
+fn load(path: &str, errors: &mut Vec<String>) -> Option<LevelPack> {
+    let file = match fs::read_to_string(path) {
+        Ok(file) => file,
+        Err(error) => {
+            errors.push(format!("{path}: {error}"));
+            return None;
+        }
+    };
+    match ron::from_str::<LevelPack>(&file) {
+        Ok(pack) => Some(pack),
+        Err(error) => {
+            errors.push(format!("{path}: {error}"));
+            None
+        }
+    }
+}
+
+fn check_level(level: &LevelData) -> Vec<String> {
+    let mut errors = check_board(&level.puzzle);
+    if level.name.trim().is_empty() {
+        errors.push("level has no name".to_string());
+    }
+    let (width, height) = (level.puzzle.width, level.puzzle.height);
+    if level.objective.uses_solution() || !level.solution.is_empty() {
+        errors.extend(check_goal("solution", &level.solution, width, height));
+    }
+    errors.extend(check_objective(&level.objective, width, height));
+    errors.extend(check_presets(level));
+    let max = level.max.unwrap_or(MAX_GENERATIONS);
+    if max < 2 {
+        errors.push(format!("a max of {max} generations leaves no room to step"));
+    }
+    if let Some(par) = level.par
+        && par >= max
+    {
+        errors.push(format!(
+            "par of {par} steps doesn't fit in {max} generations"
+        ));
+    }
+    errors
+}
+
+/// Prints how the solver fared on `level`, and returns the error when it
+/// proves the level can't be solved.
+fn solve_level(level: &LevelData, at: &str) -> Option<String> {
+    let name = format!("{at} ({})", level.name);
+    // Only exact goals can be searched for.
+    if !level.objective.is_solution() {
+        println!("{name}: skipped, only levels matching a solution can be solved");
+        return None;
+    }
+    let board = &level.puzzle;
+    let max = level.max.unwrap_or(MAX_GENERATIONS);
+    let verdict = solve(
+        &board.tiles,
+        &level.solution,
+        board.layout(),
+        max,
+        SOLVER_BUDGET,
+        &board.preset_rules(),
+        board.rules_per_color,
+    );
+    match verdict {
+        Verdict::Solved(solution) => {
+            let minimal = if solution.minimal {
+                "minimal"
+            } else {
+                "shorter runs not ruled out"
+            };
+            println!("{name}: solved in {} steps, {minimal}", solution.steps);
+            None
+        }
+        Verdict::Undecided => {
+            println!("{name}: undecided");
+            None
+        }
+        Verdict::Unsolvable => Some(format!("can't be solved in {max} generations")),
+    }
+}
+
+/// A board `width` by `height` the player has to reach, any color cells,
+/// walls and holes allowed.
+fn check_goal(name: &str, tiles: &[u8], width: usize, height: usize) -> Vec<String> {
+    let mut errors = vec![];
+    if tiles.len() != width * height {
+        errors.push(format!(
+            "{} {name} cells don't fill a {width}x{height} grid",
+            tiles.len()
+        ));
+    }
+    for (i, &value) in tiles.iter().enumerate() {
+        if value != ANY && !is_fixed(value) && Tile::from_u8(value) == Tile::Empty {
+            errors.push(format!(
+                "{name} cell {i} holds {value}, which is neither a color, {ANY}, {WALL} nor {HOLE}"
+            ));
+        }
+    }
+    errors
+}
+
+fn check_objective(objective: &Objective, width: usize, height: usize) -> Vec<String> {
+    match objective {
+        Objective::Solution | Objective::Without(_) => vec![],
+        Objective::Board(tiles) => check_goal("board", tiles, width, height),
+        Objective::AtLeast { count, .. } if *count > width * height => vec![format!(
+            "asks for {count} tiles, but the board only has {}",
+            width * height
+        )],
+        Objective::AtLeast { .. } => vec![],
+        Objective::Pattern { width: 0, .. } => vec!["pattern is 0 tiles wide".to_string()],
+        Objective::Pattern {
+            width: pattern_width,
+            tiles,
+        } => {
+            let pattern_height = tiles.len() / pattern_width;
+            let mut errors = check_goal("pattern", tiles, *pattern_width, pattern_height);
+            if *pattern_width > width || pattern_height > height {
+                errors.push(format!(
+                    "{pattern_width}x{pattern_height} pattern doesn't fit on the board"
+                ));
+            }
+            errors
+        }
+        Objective::Sequence(steps) if steps.is_empty() => {
+            vec!["sequence has no objectives".to_string()]
+        }
+        Objective::Sequence(steps) => steps
+            .iter()
+            .flat_map(|step| check_objective(step, width, height))
+            .collect(),
+    }
+}
+
+/// Preset rules can only use colors the player can pick, and have to fit in
+/// the rules the level allows each color.
+fn check_presets(level: &LevelData) -> Vec<String> {
+    let mut errors = vec![];
+    let board = &level.puzzle;
+    let mut goals = level.solution.clone();
+    goals.extend(level.objective.colors());
+    let mut pool = color_pool(&board.tiles, &goals);
+    pool.extend(board.rules.iter().map(|preset| preset.color));
+
+    let mut per_color: HashMap<Tile, usize> = HashMap::new();
+    for (index, preset) in board.rules.iter().enumerate() {
+        let color = preset.color;
+        let name = format!("preset rule {index} ({color:?})");
+        if color == Tile::Empty {
+            errors.push(format!("{name} belongs to no color"));
+        }
+        *per_color.entry(color).or_default() += 1;
+        for tile in preset.tiles.iter().chain([&preset.result]).flatten() {
+            if !pool.contains(tile) || *tile == Tile::Empty {
+                errors.push(format!(
+                    "{name} uses {tile:?}, which isn't in the color pool"
+                ));
+            }
+        }
+        if preset.count.min > preset.count.max {
+            errors.push(format!(
+                "{name} asks for {} to {} neighbours",
+                preset.count.min, preset.count.max
+            ));
+        }
+        if preset.mask.len() > MAX_NEIGHBOURS {
+            errors.push(format!(
+                "{name} has {} mask entries, at most {MAX_NEIGHBOURS} fit",
+                preset.mask.len()
+            ));
+        }
+        if preset.chance == 0 || preset.chance > Rule::ALWAYS {
+            errors.push(format!("{name} has a chance of {}%", preset.chance));
+        }
+    }
+    let mut per_color: Vec<_> = per_color.into_iter().collect();
+    per_color.sort();
+    for (color, count) in per_color {
+        if count > board.rules_per_color {
+            errors.push(format!(
+                "{count} preset rules for {color:?}, but rules_per_color only allows {}",
+                board.rules_per_color
+            ));
+        }
+    }
+    errors
+}
+
+/// `render_puzzle` fits the longer side of a board into `MAX_GRID_SIZE` tiles,
+/// so a longer side would shrink them below the size levels are drawn at.
+fn check_board(board: &Board) -> Vec<String> {
+    let mut errors = vec![];
+    let (width, height) = (board.width, board.height);
+    if width == 0 || height == 0 {
+        errors.push(format!("{width}x{height} grid has no cells"));
+    } else if width.max(height) > MAX_GRID_SIZE {
+        errors.push(format!(
+            "{width}x{height} grid is too large to display, sides of at most {MAX_GRID_SIZE} fit"
+        ));
+    }
+    if board.topology == Topology::Hex && board.boundary == Boundary::Wrap && height % 2 == 1 {
+        errors.push(format!(
+            "hex grid wraps with {height} rows, but offset rows only line up with an even count"
+        ));
+    }
+    if board.topology == Topology::Hex && board.radius == Radius::Two {
+        errors.push("hex grids only have a radius of one".to_string());
+    }
+    if board.rules_per_color == 0 {
+        errors.push("rules_per_color must allow at least one rule".to_string());
+    }
+    if board.tiles.len() != width * height {
+        errors.push(format!(
+            "{} cells don't fill a {width}x{height} grid",
+            board.tiles.len()
+        ));
+    }
+    for (i, &value) in board.tiles.iter().enumerate() {
+        if !is_fixed(value) && Tile::from_u8(value) == Tile::Empty {
+            errors.push(format!(
+                "cell {i} holds {value}, which is neither a color, a wall ({WALL}) nor a hole ({HOLE})"
+            ));
+        }
+    }
+    if !board.tiles.is_empty() && board.tiles.iter().all(|&value| is_fixed(value)) {
+        errors.push("every cell is a wall or a hole".to_string());
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A level written like the ones in `assets/levels`, with `fields` added on.
+    fn level(fields: &str) -> LevelData {
+        ron::from_str(&format!(
+            "(name: \"Test\", tier: Beginner, solution: [1, 1, 1, 1], {fields})"
+        ))
+        .unwrap()
+    }
+
+    fn board(fields: &str) -> Board {
+        ron::from_str(&format!("({fields})")).unwrap()
+    }
+
+    fn mentions(errors: &[String], text: &str) -> bool {
+        errors.iter().any(|error| error.contains(text))
+    }
+
+    const PUZZLE: &str = "puzzle: (width: 2, height: 2, tiles: [0, 0, 0, 1])";
+
+    #[test]
+    fn plain_levels_pass() {
+        let errors = check_level(&level(PUZZLE));
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+
+    #[test]
+    fn hex_wraps_need_an_even_height() {
+        let odd = board(&format!(
+            "width: 4, height: 3, topology: Hex, boundary: Wrap, tiles: {:?}",
+            [0; 12]
+        ));
+        let odd = check_board(&odd);
+        assert!(mentions(&odd, "offset rows"), "{odd:?}");
+
+        let even = board(&format!(
+            "width: 4, height: 4, topology: Hex, boundary: Wrap, tiles: {:?}",
+            [0; 16]
+        ));
+        assert!(check_board(&even).is_empty());
+        // Without wrapping the offset rows never meet.
+        let open = board(&format!(
+            "width: 4, height: 3, topology: Hex, tiles: {:?}",
+            [0; 12]
+        ));
+        assert!(check_board(&open).is_empty());
+    }
+
+    #[test]
+    fn patterns_must_fit_on_the_board() {
+        let wide = Objective::Pattern {
+            width: 3,
+            tiles: vec![1; 6],
+        };
+        let errors = check_objective(&wide, 2, 2);
+        assert!(mentions(&errors, "3x2 pattern doesn't fit"), "{errors:?}");
+        assert!(check_objective(&wide, 3, 2).is_empty());
+
+        let nested = Objective::Sequence(vec![Objective::Solution, wide]);
+        assert!(mentions(&check_objective(&nested, 2, 2), "doesn't fit"));
+    }
+
+    #[test]
+    fn presets_fit_the_rules_per_color() {
+        let rules = "rules: [(color: Red, result: Some(Green)), (color: Red, result: Some(Red))]";
+        let crowded = level(&format!(
+            "puzzle: (width: 2, height: 2, tiles: [0, 0, 0, 1], {rules})"
+        ));
+        let errors = check_presets(&crowded);
+        assert!(
+            mentions(
+                &errors,
+                "2 preset rules for Red, but rules_per_color only allows 1"
+            ),
+            "{errors:?}"
+        );
+
+        let roomy = level(&format!(
+            "puzzle: (width: 2, height: 2, tiles: [0, 0, 0, 1], rules_per_color: 2, {rules})"
+        ));
+        assert!(check_presets(&roomy).is_empty());
+    }
+
+    #[test]
+    fn par_must_fit_under_the_cap() {
+        let errors = check_level(&level(&format!("{PUZZLE}, max: Some(5), par: Some(5)")));
+        assert!(
+            mentions(&errors, "par of 5 steps doesn't fit in 5 generations"),
+            "{errors:?}"
+        );
+        let errors = check_level(&level(&format!("{PUZZLE}, max: Some(5), par: Some(4)")));
+        assert!(errors.is_empty(), "{errors:?}");
+        // Without a max of its own the level gets the default cap.
+        let par = MAX_GENERATIONS;
+        let errors = check_level(&level(&format!("{PUZZLE}, par: Some({par})")));
+        assert!(mentions(&errors, "doesn't fit"), "{errors:?}");
+    }
+}
@@ -8,8 +8,9 @@ use bevy::{
 use crate::{
     game::{
         interface::{RightSidebar, calculate_color_pool, spawn_simulation_ui},
-        level::{Puzzle, Tile},
+        level::Puzzle,
         logic::{GridIterations, PlayerRules},
+        simulation::Tile,
     },
     menus::Menu,
     screens::Screen,
@@ -143,7 +144,19 @@ pub fn handle_debug_editor(
 }
 
 pub fn print_level(_: Trigger<Pointer<Click>>, grid: Res<GridIterations>) {
-    warn!("{:?}", grid.grid.last().unwrap());
+    warn!(
+        "(width: {}, height: {}, boundary: {:?}, topology: {:?}, radius: {:?}, schedule: {:?}, wall: {:?}, hole: {:?}, seed: Some({}), tiles: {:?})",
+        grid.layout.width,
+        grid.layout.height,
+        grid.layout.boundary,
+        grid.layout.topology,
+        grid.layout.radius,
+        grid.layout.schedule,
+        grid.layout.wall,
+        grid.layout.hole,
+        grid.seed,
+        grid.grid.last().unwrap()
+    );
 }
 
 pub fn editor_color_picker(tile: Option<Tile>, action: EditorColorPickerButton) -> impl Bundle {
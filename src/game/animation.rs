@@ -14,14 +14,17 @@ pub struct AnimationConfig {
     fps: u8,
     material: Handle<CustomMaterial>,
     frame_timer: Timer,
+    /// Whether the dissolve runs backwards, forming the tile instead of burning it away.
+    reverse: bool,
 }
 
 impl AnimationConfig {
-    pub fn new(material: Handle<CustomMaterial>, fps: u8) -> Self {
+    pub fn new(material: Handle<CustomMaterial>, fps: u8, reverse: bool) -> Self {
         Self {
             fps,
             material,
             frame_timer: Self::timer_from_fps(fps),
+            reverse,
         }
     }
 
@@ -40,10 +43,14 @@ fn execute_animations(
         config.frame_timer.tick(time.delta());
         if let Some(material) = materials.get_mut(config.material.id()) {
             if config.frame_timer.just_finished() {
-                if material.params.y <= 0.0 {
+                let (done, direction) = match config.reverse {
+                    true => (material.params.y >= 1.0, 1.0),
+                    false => (material.params.y <= 0.0, -1.0),
+                };
+                if done {
                     state.set(IterationState::Ready);
                 } else {
-                    material.params.y -= (1.0 / config.fps as f32) / ANIMATION_DURATION;
+                    material.params.y += direction * (1.0 / config.fps as f32) / ANIMATION_DURATION;
                     config.frame_timer = AnimationConfig::timer_from_fps(config.fps);
                 }
             }
@@ -0,0 +1,440 @@
+//! A packed backend for the [`simulation`], one bit per cell for each color.
+//!
+//! Instead of gathering the neighbours of one cell at a time, every rule is
+//! checked for 64 cells at once: each neighbour direction is a shifted copy of
+//! a color's plane, and the copies are summed with bitwise adders. Rules that
+//! roll chances and the sequential schedule depend on the order cells are
+//! visited in, so [`supports`] leaves those to the cell by cell path.
+//!
+//! [`simulation`]: super::simulation
+
+use std::{num::NonZero, ops::Range, thread};
+
+use super::simulation::{
+    self, Combinator, Grid, HOLE, Layout, MAX_NEIGHBOURS, Rule, RuleSet, Schedule, Tile, WALL,
+    rolls,
+};
+
+/// Bits needed to count up to [`MAX_NEIGHBOURS`] matching neighbours.
+const COUNT_BITS: usize = 5;
+const EMPTY: usize = Tile::Empty as usize;
+
+/// For each tile and direction, whose neighbour that way is that tile.
+type Neighbours = [[u64; MAX_NEIGHBOURS]; EMPTY + 1];
+
+/// Whether [`Planes::step_phase`] gives the same board as the cell by cell path.
+pub fn supports(layout: Layout, rules: &RuleSet) -> bool {
+    layout.schedule != Schedule::Sequential && !rolls(rules)
+}
+
+/// Splits `height` rows into at most `count` bands of about the same size.
+pub fn bands(height: usize, count: usize) -> impl Iterator<Item = Range<usize>> {
+    let rows = height.div_ceil(count.max(1)).max(1);
+    (0..height)
+        .step_by(rows)
+        .map(move |start| start..(start + rows).min(height))
+}
+
+/// Steps `phase` of `grid` in up to `count` bands of rows, each on a thread of
+/// its own. The bands only read the shared board and are stacked back up in
+/// order, so the result is the same as stepping it in one go. Only for the
+/// rules [`supports`] allows.
+pub fn step_in_bands(
+    grid: &[u8],
+    layout: Layout,
+    rules: &RuleSet,
+    phase: usize,
+    count: usize,
+) -> Grid {
+    let planes = Planes::from_grid(grid, layout);
+    // A single band needs no thread, and the web has none to start.
+    if count <= 1 {
+        return planes.step_phase(rules, phase).to_grid();
+    }
+    thread::scope(|scope| {
+        let bands: Vec<_> = bands(layout.height, count)
+            .map(|rows| scope.spawn(|| planes.step_rows(rules, phase, rows).to_grid()))
+            .collect();
+        bands
+            .into_iter()
+            .flat_map(|band| band.join().unwrap())
+            .collect()
+    })
+}
+
+/// [`simulation::run`], stepping each phase in a band per core when the rules allow it.
+pub fn run_in_bands(grid: &[u8], layout: Layout, rules: &RuleSet, max: usize) -> Vec<Grid> {
+    if !supports(layout, rules) {
+        return simulation::run(grid, layout, rules, max);
+    }
+    let count = thread::available_parallelism().map_or(1, NonZero::get);
+    let mut history = vec![grid.to_vec()];
+    while history.len() < max {
+        let mut next = history.last().unwrap().clone();
+        for phase in 0..layout.schedule.phases(layout) {
+            next = step_in_bands(&next, layout, rules, phase, count);
+        }
+        history.push(next);
+    }
+    history
+}
+
+/// A board with one plane of bits per [`Tile`], each row padded to whole words.
+/// Walls and holes sit in the plane of the tile they are seen as, and in a
+/// plane of their own that keeps rules off them.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Planes {
+    layout: Layout,
+    /// Words per row.
+    stride: usize,
+    planes: [Vec<u64>; Tile::Empty as usize + 1],
+    walls: Vec<u64>,
+    holes: Vec<u64>,
+}
+
+impl Planes {
+    pub fn from_grid(grid: &[u8], layout: Layout) -> Planes {
+        let stride = layout.width.div_ceil(64);
+        let mut planes: [Vec<u64>; EMPTY + 1] =
+            std::array::from_fn(|_| vec![0; stride * layout.height]);
+        let (mut walls, mut holes) = (planes[EMPTY].clone(), planes[EMPTY].clone());
+        for (row, cells) in grid.chunks(layout.width.max(1)).enumerate() {
+            for (column, &value) in cells.iter().enumerate() {
+                let (at, bit) = (row * stride + column / 64, 1 << (column % 64));
+                planes[layout.seen_as(value) as usize][at] |= bit;
+                match value {
+                    WALL => walls[at] |= bit,
+                    HOLE => holes[at] |= bit,
+                    _ => {}
+                }
+            }
+        }
+        Planes {
+            layout,
+            stride,
+            planes,
+            walls,
+            holes,
+        }
+    }
+
+    pub fn to_grid(&self) -> Grid {
+        let mut grid = vec![Tile::Empty as u8; self.layout.len()];
+        // Walls and holes go last, over the tile they are seen as.
+        let fixed = [(WALL, &self.walls), (HOLE, &self.holes)];
+        let planes = self
+            .planes
+            .iter()
+            .enumerate()
+            .map(|(tile, plane)| (tile as u8, plane));
+        for (tile, plane) in planes.chain(fixed) {
+            for (at, &word) in plane.iter().enumerate() {
+                let first = at / self.stride * self.layout.width + at % self.stride * 64;
+                // Visit the set bits only, most planes are mostly empty.
+                let mut bits = word;
+                while bits != 0 {
+                    grid[first + bits.trailing_zeros() as usize] = tile;
+                    bits &= bits - 1;
+                }
+            }
+        }
+        grid
+    }
+
+    /// Applies the rules to the cells `phase` of the layout's [`Schedule`]
+    /// covers, like [`simulation::step_phase`] does for the rules [`supports`] allows.
+    ///
+    /// [`simulation::step_phase`]: super::simulation::step_phase
+    pub fn step_phase(&self, rules: &RuleSet, phase: usize) -> Planes {
+        self.step_rows(rules, phase, 0..self.layout.height)
+    }
+
+    /// [`Planes::step_phase`] for a band of `rows` only, returned as a board of
+    /// just those rows. Each band reads this board alone, so bands can be
+    /// stepped in any order or at once and stacked back up in order.
+    pub fn step_rows(&self, rules: &RuleSet, phase: usize, rows: Range<usize>) -> Planes {
+        let layout = self.layout;
+        let directions = layout.directions();
+        // Only the tiles and directions some rule looks at are worth shifting.
+        let mut wanted: Vec<(usize, usize)> = vec![];
+        for rule in rules
+            .values()
+            .flatten()
+            .filter(|rule| rule.result.is_some())
+        {
+            for &tile in rule.tiles.iter().flatten() {
+                let looked_at = (0..MAX_NEIGHBOURS).filter(|&i| rule.mask[i] && directions[i]);
+                wanted.extend(looked_at.map(|i| (tile as usize, i)));
+            }
+        }
+        wanted.sort();
+        wanted.dedup();
+        let band = rows.start * self.stride..rows.end * self.stride;
+        let mut next = Planes {
+            layout: Layout {
+                height: rows.len(),
+                ..layout
+            },
+            stride: self.stride,
+            planes: std::array::from_fn(|tile| self.planes[tile][band.clone()].to_vec()),
+            walls: self.walls[band.clone()].to_vec(),
+            holes: self.holes[band.clone()].to_vec(),
+        };
+        let mut neighbours: Neighbours = [[0; MAX_NEIGHBOURS]; EMPTY + 1];
+        for row in rows {
+            let offsets = layout.topology.offsets(row);
+            let phase_cells = match layout.schedule {
+                Schedule::Checkerboard if (row + phase).is_multiple_of(2) => 0x5555_5555_5555_5555,
+                Schedule::Checkerboard => 0xaaaa_aaaa_aaaa_aaaa,
+                _ => !0,
+            };
+            for word in 0..self.stride {
+                let at = row * self.stride + word;
+                for &(tile, i) in &wanted {
+                    neighbours[tile][i] = self.neighbour_word(tile, row, word, offsets[i]);
+                }
+                let fixed = self.walls[at] | self.holes[at];
+                for (&tile, rules) in rules {
+                    let tile = tile as usize;
+                    let mut undecided = self.planes[tile][at] & phase_cells & !fixed;
+                    for rule in rules {
+                        let Some(result) = rule.result else { continue };
+                        if undecided == 0 {
+                            break;
+                        }
+                        let hit = undecided & fires(rule, &neighbours, directions);
+                        undecided &= !hit;
+                        next.planes[tile][at - band.start] &= !hit;
+                        next.planes[result as usize][at - band.start] |= hit;
+                    }
+                }
+            }
+        }
+        next
+    }
+
+    /// Whether the neighbour `(dx, dy)` away from each cell in `word` of `row` is `tile`.
+    fn neighbour_word(
+        &self,
+        tile: usize,
+        row: usize,
+        word: usize,
+        (dx, dy): (isize, isize),
+    ) -> u64 {
+        let layout = self.layout;
+        let Some(source) = layout.boundary.resolve(row as isize + dy, layout.height) else {
+            return if tile == EMPTY {
+                self.row_mask(word)
+            } else {
+                0
+            };
+        };
+        let plane = &self.planes[tile][source * self.stride..(source + 1) * self.stride];
+        let at = |word: isize| usize::try_from(word).ok().and_then(|word| plane.get(word));
+        let next = at(word as isize + dx.signum()).copied().unwrap_or(0);
+        // Bit b holds column 64 * word + b + dx of the source row.
+        let mut bits = match dx {
+            0 => plane[word],
+            1.. => (plane[word] >> dx) | (next << (64 - dx)),
+            _ => (plane[word] << -dx) | (next >> (64 + dx)),
+        };
+        // Columns whose neighbour lies past a side of the board.
+        let edge = if dx < 0 {
+            0..(-dx as usize).min(layout.width)
+        } else {
+            layout.width.saturating_sub(dx as usize)..layout.width
+        };
+        let first = 64 * word;
+        for column in edge.start.max(first)..edge.end.min(first + 64) {
+            let neighbour = match layout.boundary.resolve(column as isize + dx, layout.width) {
+                Some(column) => (plane[column / 64] >> (column % 64)) & 1 == 1,
+                None => tile == EMPTY,
+            };
+            let bit = 1 << (column - first);
+            if neighbour {
+                bits |= bit;
+            } else {
+                bits &= !bit;
+            }
+        }
+        bits & self.row_mask(word)
+    }
+
+    /// The bits of `word` that are on the board rather than padding.
+    fn row_mask(&self, word: usize) -> u64 {
+        match self.layout.width - 64 * word {
+            64.. => !0,
+            columns => (1 << columns) - 1,
+        }
+    }
+}
+
+/// The cells of a word that `rule` fires for, like [`Rule::fires`].
+fn fires(rule: &Rule, neighbours: &Neighbours, directions: [bool; MAX_NEIGHBOURS]) -> u64 {
+    // A rule without tiles never fires, whatever the combinator.
+    if rule.tiles.iter().all(Option::is_none) {
+        return 0;
+    }
+    let invert = if rule.invert { !0 } else { 0 };
+    let checks = rule.tiles.iter().flatten().map(|&tile| {
+        let mut sum = [0; COUNT_BITS];
+        for i in (0..MAX_NEIGHBOURS).filter(|&i| rule.mask[i] && directions[i]) {
+            let mut carry = neighbours[tile as usize][i];
+            for bit in &mut sum {
+                (*bit, carry) = (*bit ^ carry, *bit & carry);
+            }
+        }
+        let count = rule.count;
+        (at_least(&sum, count.min as u32) & !at_least(&sum, count.max as u32 + 1)) ^ invert
+    });
+    match rule.combinator {
+        Combinator::AnyOf => checks.fold(0, |any, check| any | check),
+        Combinator::AllOf => checks.fold(!0, |all, check| all & check),
+        Combinator::NoneOf => !checks.fold(0, |any, check| any | check),
+    }
+}
+
+/// The cells whose count in the bit-sliced `sum` is `min` or more.
+fn at_least(sum: &[u64; COUNT_BITS], min: u32) -> u64 {
+    if min >= 1 << COUNT_BITS {
+        return 0;
+    }
+    // Compare from the highest bit down, like long comparison on paper.
+    let (mut greater, mut equal) = (0, !0);
+    for (bit, &sum) in sum.iter().enumerate().rev() {
+        let wanted = if (min >> bit) & 1 == 1 { !0 } else { 0 };
+        greater |= equal & sum & !wanted;
+        equal &= !(sum ^ wanted);
+    }
+    greater | equal
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::game::simulation::{Boundary, Count, Radius, Topology, step_cells, step_phase};
+
+    fn random_rules(rng: &mut StdRng, colors: u8) -> RuleSet {
+        let tile = |rng: &mut StdRng| Tile::from_u8(rng.gen_range(0..colors));
+        (0..colors)
+            .map(|color| {
+                let rules = (0..rng.gen_range(0..4))
+                    .map(|_| {
+                        let min = rng.gen_range(0..4);
+                        Rule {
+                            tiles: [Some(tile(rng)), rng.gen_bool(0.5).then(|| tile(rng))],
+                            combinator: [Combinator::AnyOf, Combinator::AllOf, Combinator::NoneOf]
+                                [rng.gen_range(0..3)],
+                            count: Count::between(min, min + rng.gen_range(0..6)),
+                            invert: rng.gen_bool(0.2),
+                            mask: std::array::from_fn(|_| rng.gen_bool(0.7)),
+                            result: rng.gen_bool(0.9).then(|| tile(rng)),
+                            ..Rule::default()
+                        }
+                    })
+                    .collect();
+                (Tile::from_u8(color), rules)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn packed_boards_round_trip() {
+        let layout = Layout::new(70, 3);
+        let values: Vec<u8> = (0..9).chain([WALL, HOLE]).collect();
+        let grid: Grid = (0..layout.len())
+            .map(|i| values[i % values.len()])
+            .collect();
+        assert_eq!(Planes::from_grid(&grid, layout).to_grid(), grid);
+    }
+
+    #[test]
+    fn counts_compare_bit_by_bit() {
+        // Cells counting 0, 1, 2, ... 31 in the first 32 bits.
+        let sum = std::array::from_fn(|bit| {
+            (0..32)
+                .filter(|count| (count >> bit) & 1 == 1)
+                .fold(0, |sum, count| sum | (1 << count))
+        });
+        assert_eq!(at_least(&sum, 0) as u32, u32::MAX);
+        assert_eq!(at_least(&sum, 3) as u32, u32::MAX << 3);
+        assert_eq!(at_least(&sum, 32), 0);
+    }
+
+    #[test]
+    fn matches_the_cell_by_cell_path() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let boundaries = [Boundary::Empty, Boundary::Wrap, Boundary::Mirror];
+        let topologies = [Topology::Square, Topology::Hex];
+        let radii = [Radius::One, Radius::Two];
+        let schedules = [Schedule::Sync, Schedule::Checkerboard];
+        for round in 0..200 {
+            let layout = Layout::new(rng.gen_range(1..80), 2 * rng.gen_range(1..5))
+                .with_boundary(boundaries[round % 3])
+                .with_topology(topologies[round / 3 % 2])
+                .with_radius(radii[round / 6 % 2])
+                .with_schedule(schedules[round / 12 % 2])
+                .with_walls(
+                    Tile::from_u8(rng.gen_range(0..9)),
+                    Tile::from_u8(rng.gen_range(0..9)),
+                );
+            let colors = rng.gen_range(2..=9);
+            let rules = random_rules(&mut rng, colors);
+            let grid: Grid = (0..layout.len())
+                .map(|_| match rng.gen_range(0..colors + 2) {
+                    value if value == colors => WALL,
+                    value if value > colors => HOLE,
+                    value => value,
+                })
+                .collect();
+            let phases = layout.schedule.phases(layout);
+            for phase in 0..phases {
+                let planes = Planes::from_grid(&grid, layout);
+                let cells = step_cells(&grid, layout, &rules, phase, &mut rng);
+                assert_eq!(planes.step_phase(&rules, phase).to_grid(), cells);
+                // Bands stacked back up give the same board.
+                let middle = layout.height / 2;
+                let bands = [0..middle, middle..layout.height]
+                    .map(|rows| planes.step_rows(&rules, phase, rows).to_grid());
+                assert_eq!(bands.concat(), cells, "{layout:?} phase {phase}");
+            }
+        }
+        // Rolls and sequential updates stay on the cell by cell path.
+        let sequential = Layout::new(4, 4).with_schedule(Schedule::Sequential);
+        assert!(!supports(sequential, &RuleSet::new()));
+        let grid = vec![0; 16];
+        let rules = random_rules(&mut rng, 3);
+        assert_eq!(
+            step_phase(&grid, sequential, &rules, 0, &mut StdRng::seed_from_u64(0)),
+            step_cells(&grid, sequential, &rules, 0, &mut StdRng::seed_from_u64(0))
+        );
+    }
+
+    #[test]
+    fn bands_match_a_single_step() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let schedules = [Schedule::Sync, Schedule::Checkerboard];
+        for round in 0..40 {
+            let layout = Layout::new(rng.gen_range(1..90), rng.gen_range(1..40))
+                .with_boundary([Boundary::Empty, Boundary::Wrap, Boundary::Mirror][round % 3])
+                .with_schedule(schedules[round / 3 % 2]);
+            let rules = random_rules(&mut rng, 4);
+            let grid: Grid = (0..layout.len()).map(|_| rng.gen_range(0..4)).collect();
+            let stepped = simulation::step(&grid, layout, &rules);
+            // More bands than rows leaves a row to each.
+            for count in [1, 2, 3, 8, layout.height, layout.height + 5] {
+                let mut banded = grid.clone();
+                for phase in 0..layout.schedule.phases(layout) {
+                    banded = step_in_bands(&banded, layout, &rules, phase, count);
+                }
+                assert_eq!(banded, stepped, "{layout:?} in {count} bands");
+            }
+            assert_eq!(
+                run_in_bands(&grid, layout, &rules, 4),
+                simulation::run(&grid, layout, &rules, 4)
+            );
+        }
+    }
+}
@@ -1,13 +1,16 @@
 use super::{
-    level::{Tile, spawn_level},
+    level::spawn_level,
     logic::PlayerRules,
+    pack::LevelRegistry,
+    simulation::{self, Rule, Settled, Tile},
+    undo::{RuleHistory, handle_undo_keys, record_rule_edits, remember_rules, undo_buttons},
 };
 // use bevy_egui::{EguiContextPass, EguiContextSettings, EguiContexts, EguiPlugin, egui};
 use super::{
-    level::{Grid, Level, LevelAssets, Switch},
+    level::{CurrentLevel, Level, LevelAssets, LevelData},
     logic::{
-        AutomaticSimulation, DisableControls, GridIterations, Rule, Victory, reset_simulation,
-        step_through, toggle_simulation,
+        AutomaticSimulation, DisableControls, GridIterations, IterationState, Playback, Victory,
+        after_jump, reset_simulation, step_back, step_through, toggle_simulation,
     },
 };
 use crate::menus::tutorial::spawn_tutorial_menu;
@@ -15,14 +18,14 @@ use crate::{
     menus::Menu,
     screens::Screen,
     theme::{
-        palette::{BUTTON_PRESSED_BACKGROUND, BUTTON_PRESSED_BACKGROUND_ALT},
+        palette::{BUTTON_PRESSED_BACKGROUND, BUTTON_PRESSED_BACKGROUND_ALT, HEADER_TEXT, SOCKET},
         prelude::InteractionPalette,
         widget::{self, BUTTON_COLORS_ALT, BUTTON_SIZE_ALT, ButtonColors, ButtonSize},
     },
 };
 use Val::Px;
-use bevy::{ecs::spawn::SpawnIter, prelude::*};
-use std::cmp::PartialOrd;
+use bevy::{prelude::*, ui::RelativeCursorPosition};
+use std::collections::HashMap;
 
 pub(super) fn plugin(app: &mut App) {
     // app.add_plugins(EguiPlugin::default());
@@ -35,6 +38,9 @@ pub(super) fn plugin(app: &mut App) {
         Update,
         (
             spawn_rules_ui.run_if(resource_changed::<PlayerRules>),
+            update_settled_label.run_if(resource_changed::<GridIterations>),
+            update_timeline.run_if(resource_changed::<GridIterations>),
+            handle_scrubber,
             update_button_text.run_if(
                 resource_added::<AutomaticSimulation>
                     .or(resource_added::<DisableControls>)
@@ -44,15 +50,31 @@ pub(super) fn plugin(app: &mut App) {
                     .or(resource_removed::<Victory>),
             ),
             (
-                handle_mask_buttons.run_if(resource_exists::<MousePainting>),
-                handle_invert_buttons,
-                handle_reset_buttons,
-                handle_color_pickers,
-                toggle_mouse_painting,
+                handle_undo_keys,
+                remember_rules,
+                (
+                    handle_mask_buttons.run_if(resource_exists::<MousePainting>),
+                    handle_invert_buttons,
+                    handle_combinator_buttons,
+                    handle_chance_buttons,
+                    handle_reset_buttons,
+                    handle_color_pickers,
+                    handle_count_buttons,
+                    handle_add_rule_buttons,
+                    handle_remove_rule_buttons,
+                    handle_move_rule_buttons,
+                    toggle_mouse_painting,
+                ),
+                record_rule_edits,
             )
+                .chain()
                 .run_if(not(resource_exists::<DisableControls>)),
         )
-            .run_if(in_state(Screen::Gameplay).and(in_state(Menu::None))),
+            .run_if(
+                in_state(Screen::Gameplay)
+                    .or(in_state(Screen::Sandbox))
+                    .and(in_state(Menu::None)),
+            ),
     );
     app.add_systems(
         OnEnter(Screen::Gameplay),
@@ -67,6 +89,7 @@ pub(super) fn plugin(app: &mut App) {
     app.add_systems(Update, update_ui_scale);
     app.add_observer(change_font);
     app.init_resource::<HelpSeen>();
+    app.init_resource::<RuleHistory>();
     app.init_resource::<ReachedLevel>();
 }
 
@@ -74,6 +97,7 @@ fn spawn_rules_ui(
     mut commands: Commands,
     sidebar: Single<(Entity, Option<&Children>), With<RulesWidget>>,
     player_rules: Res<PlayerRules>,
+    grid_iter: Res<GridIterations>,
 ) {
     let (entity, children) = sidebar.into_inner();
     if let Some(children) = children {
@@ -81,26 +105,56 @@ fn spawn_rules_ui(
             commands.entity(child).despawn();
         }
     }
-    let mut rules: Vec<_> = player_rules.rules.keys().collect();
-    rules.sort();
+    let mut tiles: Vec<_> = player_rules.rules.keys().copied().collect();
+    tiles.sort();
 
-    let rule_widgets: Vec<_> = rules
-        .into_iter()
-        .map(|tile| {
-            let rule = &player_rules.rules[tile];
-            widget::rule_ui(*tile, rule.clone())
-        })
-        .collect();
-    commands
-        .entity(entity)
-        .insert(Children::spawn(SpawnIter(rule_widgets.into_iter())));
+    let layout = grid_iter.layout;
+    commands.entity(entity).with_children(|parent| {
+        for tile in tiles {
+            let rules = &player_rules.rules[&tile];
+            for (index, rule) in rules.iter().enumerate() {
+                parent.spawn(widget::rule_ui(
+                    tile,
+                    index,
+                    rules.len(),
+                    rule.clone(),
+                    layout,
+                ));
+            }
+            if rules.len() < player_rules.max_rules {
+                parent.spawn(widget::add_rule_button(tile));
+            }
+        }
+    });
 }
 
 pub fn spawn_simulation_ui(
     mut commands: Commands,
     reached: Res<ReachedLevel>,
     help_seen: Res<HelpSeen>,
+    current_level: CurrentLevel,
+    level: Res<State<Level>>,
+    registry: Res<LevelRegistry>,
 ) {
+    let level = *level.get();
+    let furthest = registry
+        .packs
+        .get(level.pack)
+        .map_or(0, |pack| reached.get(&pack.title));
+    let unlocked = |help: Help| {
+        if registry
+            .up_to(Level {
+                index: furthest,
+                ..level
+            })
+            .iter()
+            .any(|level| level.help.contains(&help))
+        {
+            Visibility::default()
+        } else {
+            Visibility::Hidden
+        }
+    };
     commands.spawn((
         widget::ui_row("Gameplay UI"),
         GlobalZIndex(1),
@@ -128,46 +182,51 @@ pub fn spawn_simulation_ui(
                             row_gap: Px(16.0),
                             ..default()
                         },
-                        children![(
-                            Node {
-                                display: Display::Flex,
-                                flex_direction: FlexDirection::Row,
-                                column_gap: Px(8.0),
-                                ..default()
-                            },
-                            children![
-                                widget::button_custom(
-                                    "",
-                                    toggle_simulation,
-                                    Some(BUTTON_COLORS_ALT),
-                                    Some(ButtonSize {
-                                        width: 120.0,
-                                        height: BUTTON_SIZE_ALT.height
-                                    })
-                                ),
-                                (
+                        children![
+                            (
+                                Node {
+                                    display: Display::Flex,
+                                    flex_direction: FlexDirection::Row,
+                                    column_gap: Px(8.0),
+                                    ..default()
+                                },
+                                children![
+                                    widget::button_custom(
+                                        "",
+                                        toggle_simulation,
+                                        Some(BUTTON_COLORS_ALT),
+                                        Some(ButtonSize {
+                                            width: 120.0,
+                                            height: BUTTON_SIZE_ALT.height
+                                        })
+                                    ),
+                                    (
+                                        widget::button_custom(
+                                            "󰑙",
+                                            reset_simulation,
+                                            None,
+                                            Some(BUTTON_SIZE_ALT)
+                                        ),
+                                        LockReset
+                                    ),
                                     widget::button_custom(
-                                        "󰑙",
-                                        reset_simulation,
+                                        "",
+                                        step_through,
                                         None,
                                         Some(BUTTON_SIZE_ALT)
                                     ),
-                                    LockReset
-                                ),
-                                widget::button_custom(
-                                    "",
-                                    step_through,
-                                    None,
-                                    Some(BUTTON_SIZE_ALT)
-                                ),
-                                (widget::button_custom(
-                                    "",
-                                    go_next_level,
-                                    None,
-                                    Some(BUTTON_SIZE_ALT)
-                                ),),
-                            ],
-                        ),],
+                                    (widget::button_custom(
+                                        "",
+                                        go_next_level,
+                                        None,
+                                        Some(BUTTON_SIZE_ALT)
+                                    ),),
+                                ],
+                            ),
+                            undo_buttons(),
+                            timeline(),
+                            settled_label(),
+                        ],
                     ),
                 ],
             ),
@@ -187,70 +246,211 @@ pub fn spawn_simulation_ui(
                         row_gap: Px(15.0),
                         ..default()
                     },
-                    children![(
-                        Node {
-                            display: Display::Flex,
-                            flex_direction: FlexDirection::Row,
-                            column_gap: Px(8.0),
-                            ..default()
-                        },
-                        children![
-                            (
-                                Help::General,
-                                widget::button_custom(
-                                    "",
-                                    spawn_tutorial_menu,
-                                    help_new(&help_seen, Help::General as usize),
-                                    Some(BUTTON_SIZE_ALT)
+                    children![
+                        level_info(current_level.get()),
+                        (
+                            Node {
+                                display: Display::Flex,
+                                flex_direction: FlexDirection::Row,
+                                column_gap: Px(8.0),
+                                ..default()
+                            },
+                            children![
+                                (
+                                    Help::General,
+                                    widget::button_custom(
+                                        "",
+                                        spawn_tutorial_menu,
+                                        help_new(&help_seen, Help::General as usize),
+                                        Some(BUTTON_SIZE_ALT)
+                                    ),
                                 ),
-                            ),
-                            (
-                                Help::Winning,
-                                widget::button_custom(
-                                    "",
-                                    spawn_tutorial_menu,
-                                    help_new(&help_seen, Help::Winning as usize),
-                                    Some(BUTTON_SIZE_ALT)
+                                (
+                                    Help::Winning,
+                                    widget::button_custom(
+                                        "",
+                                        spawn_tutorial_menu,
+                                        help_new(&help_seen, Help::Winning as usize),
+                                        Some(BUTTON_SIZE_ALT)
+                                    ),
                                 ),
-                            ),
-                            (
-                                Help::Search,
-                                widget::button_custom(
-                                    "󱈅",
-                                    spawn_tutorial_menu,
-                                    help_new(&help_seen, Help::Search as usize),
-                                    Some(BUTTON_SIZE_ALT)
+                                (
+                                    Help::Search,
+                                    widget::button_custom(
+                                        "󱈅",
+                                        spawn_tutorial_menu,
+                                        help_new(&help_seen, Help::Search as usize),
+                                        Some(BUTTON_SIZE_ALT)
+                                    ),
+                                    unlocked(Help::Search)
                                 ),
-                                if reached.0 >= Level::Intermediate1 {
-                                    Visibility::default()
-                                } else {
-                                    Visibility::Hidden
-                                }
-                            ),
-                            (
-                                Help::Negate,
-                                widget::button_custom(
-                                    "",
-                                    spawn_tutorial_menu,
-                                    help_new(&help_seen, Help::Negate as usize),
-                                    Some(BUTTON_SIZE_ALT)
+                                (
+                                    Help::Negate,
+                                    widget::button_custom(
+                                        "",
+                                        spawn_tutorial_menu,
+                                        help_new(&help_seen, Help::Negate as usize),
+                                        Some(BUTTON_SIZE_ALT)
+                                    ),
+                                    unlocked(Help::Negate)
                                 ),
-                                if reached.0 >= Level::Intermediate5 {
-                                    Visibility::default()
-                                } else {
-                                    Visibility::Hidden
-                                }
-                            ),
-                        ],
-                    ),],
+                            ],
+                        ),
+                    ],
                     RightSidebar
                 )],
             )
         ],
     ));
 }
-#[derive(Resource, Default, Eq, Hash, PartialEq, PartialOrd, Ord)]
-pub struct ReachedLevel(Level);
+/// The level's name, tier and objective, with its par and hint when it has them.
+fn level_info(level: Option<&LevelData>) -> impl Bundle {
+    let (name, mut details, objective, hint) = match level {
+        Some(level) => (
+            level.name.clone(),
+            level.tier.name().to_string(),
+            level.objective.describe(),
+            level.hint.clone().unwrap_or_default(),
+        ),
+        None => Default::default(),
+    };
+    if let Some(par) = level.and_then(|level| level.par) {
+        details += &format!(", par {par}");
+    }
+    (
+        Name::new("Level Info"),
+        Node {
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: Px(8.0),
+            ..default()
+        },
+        children![
+            widget::header(name),
+            widget::label(details),
+            (
+                widget::label(objective),
+                TextLayout::new_with_justify(JustifyText::Center),
+            ),
+            (
+                widget::label(hint),
+                TextLayout::new_with_justify(JustifyText::Center)
+            ),
+        ],
+    )
+}
+/// The bar over every board of the run, filled up to the one on show.
+#[derive(Component)]
+struct Scrubber;
+#[derive(Component)]
+struct ScrubberFill;
+/// Which board of the run is on show, out of how many.
+#[derive(Component)]
+struct TimelineLabel;
+/// Stepping back, and scrubbing to any board the run has been through.
+pub fn timeline() -> impl Bundle {
+    (
+        Name::new("Timeline"),
+        Node {
+            display: Display::Flex,
+            flex_direction: FlexDirection::Row,
+            align_items: AlignItems::Center,
+            column_gap: Px(16.0),
+            ..default()
+        },
+        children![
+            widget::button_custom("", step_back, None, Some(BUTTON_SIZE_ALT)),
+            (
+                Name::new("Scrubber"),
+                Node {
+                    width: Px(200.0),
+                    height: Px(16.0),
+                    ..default()
+                },
+                BackgroundColor(SOCKET),
+                BorderRadius::all(Px(8.0)),
+                Interaction::default(),
+                RelativeCursorPosition::default(),
+                Scrubber,
+                children![(
+                    Node {
+                        width: Val::Percent(0.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BackgroundColor(HEADER_TEXT),
+                    BorderRadius::all(Px(8.0)),
+                    ScrubberFill,
+                )],
+            ),
+            (widget::label(""), TimelineLabel),
+        ],
+    )
+}
+fn update_timeline(
+    grid: Res<GridIterations>,
+    mut fill: Single<&mut Node, With<ScrubberFill>>,
+    mut label: Single<&mut Text, With<TimelineLabel>>,
+) {
+    let (shown, last) = (grid.shown(), grid.history_len() - 1);
+    fill.width = Val::Percent(100.0 * shown as f32 / last.max(1) as f32);
+    label.0 = format!("{shown} / {last}");
+}
+/// Jumps to the board under the cursor while the scrubber is held down.
+fn handle_scrubber(
+    mut commands: Commands,
+    scrubber: Single<(&Interaction, &RelativeCursorPosition), With<Scrubber>>,
+    rules: Res<PlayerRules>,
+    mut grid: ResMut<GridIterations>,
+    mut state: ResMut<NextState<IterationState>>,
+) {
+    let (interaction, cursor) = scrubber.into_inner();
+    let Some(cursor) = cursor
+        .normalized
+        .filter(|_| *interaction == Interaction::Pressed)
+    else {
+        return;
+    };
+    let last = grid.history_len() - 1;
+    let index = (cursor.x.clamp(0.0, 1.0) * last as f32).round() as usize;
+    if index == grid.shown() {
+        return;
+    }
+    commands.remove_resource::<AutomaticSimulation>();
+    grid.jump_to(index, &rules.rules);
+    grid.playback = Playback::Jump;
+    after_jump(&mut commands, &grid);
+    state.set(IterationState::Displaying);
+}
+/// Tells the player when the board stopped changing or started to repeat.
+#[derive(Component)]
+struct SettledLabel;
+pub fn settled_label() -> impl Bundle {
+    (widget::label(""), SettledLabel)
+}
+fn update_settled_label(
+    grid: Res<GridIterations>,
+    mut label: Single<&mut Text, With<SettledLabel>>,
+) {
+    label.0 = match grid.settled {
+        Some(Settled::Stable { after: 1 }) => "Stable after 1 step".to_string(),
+        Some(Settled::Stable { after }) => format!("Stable after {after} steps"),
+        Some(Settled::Loop { period }) => format!("Loops with period {period}"),
+        None => String::new(),
+    };
+}
+/// The furthest level reached in each pack, by the pack's title.
+#[derive(Resource, Default)]
+pub struct ReachedLevel(HashMap<String, usize>);
+impl ReachedLevel {
+    pub fn get(&self, pack: &str) -> usize {
+        self.0.get(pack).copied().unwrap_or(0)
+    }
+    fn reach(&mut self, pack: &str, index: usize) {
+        let furthest = self.0.entry(pack.to_string()).or_default();
+        *furthest = index.max(*furthest);
+    }
+}
 #[derive(Resource, Default)]
 pub struct HelpSeen(pub [bool; 4]);
 fn help_new(help_seen: &HelpSeen, index: usize) -> Option<ButtonColors> {
@@ -260,7 +460,7 @@ fn help_new(help_seen: &HelpSeen, index: usize) -> Option<ButtonColors> {
         Some(BUTTON_COLORS_ALT)
     }
 }
-#[derive(Component, Copy, Clone)]
+#[derive(Component, Copy, Clone, Eq, PartialEq, Debug, serde::Deserialize)]
 pub enum Help {
     General,
     Winning,
@@ -270,13 +470,16 @@ pub enum Help {
 pub fn go_next_level(
     _: Trigger<Pointer<Click>>,
     current_level: Res<State<Level>>,
+    registry: Res<LevelRegistry>,
     mut level: ResMut<NextState<Level>>,
     mut screen: ResMut<NextState<Screen>>,
     mut reached: ResMut<ReachedLevel>,
 ) {
-    let next_level = current_level.get().next();
+    let next_level = registry.next(*current_level.get());
     level.set(next_level);
-    reached.0 = next_level.max(reached.0);
+    if let Some(pack) = registry.packs.get(next_level.pack) {
+        reached.reach(&pack.title, next_level.index);
+    }
     screen.set(Screen::Loading);
 }
 
@@ -382,25 +585,70 @@ pub struct RightSidebar;
 #[derive(Component)]
 pub struct LockReset;
 
+/// Rule buttons point at `PlayerRules.rules[tile][rule]`.
 #[derive(Component)]
 pub struct MaskToggleButton {
     pub tile: Tile,
+    pub rule: usize,
     pub index: usize,
 }
 
 #[derive(Component)]
 pub struct InvertToggleButton {
     pub tile: Tile,
+    pub rule: usize,
+}
+
+/// Cycles a rule between any-of, all-of and none-of its trigger colors.
+#[derive(Component)]
+pub struct CombinatorButton {
+    pub tile: Tile,
+    pub rule: usize,
+}
+
+/// Steps a rule's chance down through [`CHANCES`].
+#[derive(Component)]
+pub struct ChanceButton {
+    pub tile: Tile,
+    pub rule: usize,
 }
 
 #[derive(Component)]
 pub struct ResetRuleButton {
     pub tile: Tile,
+    pub rule: usize,
+}
+
+/// Steps the lower or upper bound of a rule's neighbour count.
+#[derive(Component)]
+pub struct CountButton {
+    pub tile: Tile,
+    pub rule: usize,
+    pub max: bool,
+}
+
+#[derive(Component)]
+pub struct AddRuleButton {
+    pub tile: Tile,
+}
+
+#[derive(Component)]
+pub struct RemoveRuleButton {
+    pub tile: Tile,
+    pub rule: usize,
+}
+
+#[derive(Component)]
+pub struct MoveRuleButton {
+    pub tile: Tile,
+    pub rule: usize,
+    pub up: bool,
 }
 
 #[derive(Component, Default, Debug)]
 pub struct ColorPickerButton {
     pub tile: Tile,
+    pub rule: usize,
     pub index: usize,
     pub color: Option<Tile>,
 }
@@ -442,7 +690,7 @@ fn toggle_mouse_painting(
     } else {
         commands.remove_resource::<MousePainting>();
         if mouse_input.just_released(MouseButton::Left) {
-            for (_, rule) in &mut rules.rules {
+            for rule in rules.rules.values_mut().flatten() {
                 rule.changed.fill(false);
             }
         }
@@ -456,8 +704,11 @@ fn handle_mask_buttons(
 ) {
     for (interaction, button) in &interaction_query {
         if *interaction != Interaction::None {
-            if let Some(rule) = rules.rules.get_mut(&button.tile) {
-                if !rule.changed[button.index] && mouse_input.pressed(MouseButton::Left) {
+            if let Some(rule) = rule_mut(&mut rules, button.tile, button.rule) {
+                if !rule.changed[button.index]
+                    && !rule.locked.mask()
+                    && mouse_input.pressed(MouseButton::Left)
+                {
                     rule.changed[button.index] = true;
                     rule.mask[button.index] = !rule.mask[button.index];
                 }
@@ -471,10 +722,43 @@ fn handle_invert_buttons(
     mut rules: ResMut<PlayerRules>,
 ) {
     for (interaction, button) in &interaction_query {
-        if *interaction == Interaction::Pressed {
-            if let Some(rule) = rules.rules.get_mut(&button.tile) {
-                rule.invert = !rule.invert;
-            }
+        if *interaction == Interaction::Pressed
+            && let Some(rule) = rule_mut(&mut rules, button.tile, button.rule)
+            && !rule.locked.invert()
+        {
+            rule.invert = !rule.invert;
+        }
+    }
+}
+
+fn handle_combinator_buttons(
+    interaction_query: Query<(&Interaction, &CombinatorButton), Changed<Interaction>>,
+    mut rules: ResMut<PlayerRules>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction == Interaction::Pressed
+            && let Some(rule) = rule_mut(&mut rules, button.tile, button.rule)
+            && !rule.locked.rule
+        {
+            rule.combinator = rule.combinator.next();
+        }
+    }
+}
+
+/// The chances a rule can be given, in the order the chance button cycles them.
+pub const CHANCES: [u8; 4] = [Rule::ALWAYS, 75, 50, 25];
+
+fn handle_chance_buttons(
+    interaction_query: Query<(&Interaction, &ChanceButton), Changed<Interaction>>,
+    mut rules: ResMut<PlayerRules>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction == Interaction::Pressed
+            && let Some(rule) = rule_mut(&mut rules, button.tile, button.rule)
+            && !rule.locked.rule
+        {
+            let next = CHANCES.iter().position(|&chance| chance == rule.chance);
+            rule.chance = CHANCES[next.map_or(0, |index| (index + 1) % CHANCES.len())];
         }
     }
 }
@@ -484,14 +768,108 @@ fn handle_reset_buttons(
     mut rules: ResMut<PlayerRules>,
 ) {
     for (interaction, button) in &interaction_query {
-        if *interaction == Interaction::Pressed {
-            if let Some(rule) = rules.rules.get_mut(&button.tile) {
-                *rule = Rule::default();
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        // Rules go back to the preset they started as, blank if they were added.
+        let defaults = rules
+            .defaults
+            .get(&button.tile)
+            .cloned()
+            .unwrap_or_default();
+        if let Some(rule) = rule_mut(&mut rules, button.tile, button.rule)
+            && !rule.locked.rule
+        {
+            *rule = rule
+                .preset
+                .and_then(|preset| defaults.get(preset))
+                .cloned()
+                .unwrap_or_default();
+        }
+    }
+}
+
+fn handle_count_buttons(
+    interaction_query: Query<(&Interaction, &CountButton), Changed<Interaction>>,
+    mut rules: ResMut<PlayerRules>,
+    grid_iter: Res<GridIterations>,
+) {
+    let limit = grid_iter.layout.neighbour_count();
+    for (interaction, button) in &interaction_query {
+        if *interaction == Interaction::Pressed
+            && let Some(rule) = rule_mut(&mut rules, button.tile, button.rule)
+            && !rule.locked.rule
+        {
+            // Both bounds wrap around, dragging the other one along so min never passes max.
+            // Bounds only go up to the neighbours a cell has on this board.
+            let count = &mut rule.count;
+            if button.max {
+                count.max = (count.max.min(limit) + 1) % (limit + 1);
+                count.min = count.min.min(count.max);
+            } else {
+                count.min = (count.min.min(limit) + 1) % (limit + 1);
+                count.max = count.max.max(count.min);
             }
         }
     }
 }
 
+fn handle_add_rule_buttons(
+    interaction_query: Query<(&Interaction, &AddRuleButton), Changed<Interaction>>,
+    mut rules: ResMut<PlayerRules>,
+) {
+    let max_rules = rules.max_rules;
+    for (interaction, button) in &interaction_query {
+        if *interaction == Interaction::Pressed
+            && let Some(list) = rules.rules.get_mut(&button.tile)
+            && list.len() < max_rules
+        {
+            list.push(Rule::default());
+        }
+    }
+}
+
+fn handle_remove_rule_buttons(
+    interaction_query: Query<(&Interaction, &RemoveRuleButton), Changed<Interaction>>,
+    mut rules: ResMut<PlayerRules>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction == Interaction::Pressed
+            && let Some(list) = rules.rules.get_mut(&button.tile)
+            && list.len() > 1
+            && list.get(button.rule).is_some_and(|rule| !rule.locked.any())
+        {
+            list.remove(button.rule);
+        }
+    }
+}
+
+fn handle_move_rule_buttons(
+    interaction_query: Query<(&Interaction, &MoveRuleButton), Changed<Interaction>>,
+    mut rules: ResMut<PlayerRules>,
+) {
+    for (interaction, button) in &interaction_query {
+        let other = if button.up {
+            button.rule.checked_sub(1)
+        } else {
+            Some(button.rule + 1)
+        };
+        if *interaction == Interaction::Pressed
+            && let Some(list) = rules.rules.get_mut(&button.tile)
+            && let Some(other) = other.filter(|&other| other < list.len())
+            // Locked rules keep their place, so nothing swaps with them either.
+            && !list[button.rule].locked.any()
+            && !list[other].locked.any()
+        {
+            list.swap(button.rule, other);
+        }
+    }
+}
+
+fn rule_mut(rules: &mut PlayerRules, tile: Tile, rule: usize) -> Option<&mut Rule> {
+    rules.rules.get_mut(&tile)?.get_mut(rule)
+}
+
 fn handle_color_pickers(
     mut interaction_query: Query<(&Interaction, &mut ColorPickerButton), Changed<Interaction>>,
     mut rules: ResMut<PlayerRules>,
@@ -499,10 +877,12 @@ fn handle_color_pickers(
     let color_pool = &rules.color_pool.clone();
     for (interaction, mut button) in &mut interaction_query {
         if *interaction == Interaction::Pressed {
-            if let Some(rule) = rules.rules.get_mut(&button.tile) {
+            if let Some(rule) = rule_mut(&mut rules, button.tile, button.rule) {
                 match button.index {
+                    0 | 1 if rule.locked.tiles() => {}
                     0 => rule.tiles[0] = button.change_color(color_pool),
                     1 => rule.tiles[1] = button.change_color(color_pool),
+                    2 if rule.locked.result() => {}
                     2 => rule.result = button.change_color(color_pool),
                     _ => {}
                 };
@@ -511,19 +891,23 @@ fn handle_color_pickers(
     }
 }
 
-pub fn calculate_color_pool(grid_iter: Res<GridIterations>, mut rules: ResMut<PlayerRules>) {
-    let mut color_pool: Grid = default();
-    color_pool.extend(grid_iter.grid.last().unwrap());
-    color_pool.extend(grid_iter.goal.clone());
+/// Colors with preset rules keep them, even when they aren't on either board.
+pub fn calculate_color_pool(grid_iter: Res<GridIterations>, rules: ResMut<PlayerRules>) {
+    let rules = rules.into_inner();
+    let mut goals = grid_iter.goal.clone();
+    goals.extend(grid_iter.objective.colors());
+    let mut color_pool = simulation::color_pool(grid_iter.grid.last().unwrap(), &goals);
+    color_pool.extend(rules.defaults.keys());
     color_pool.sort();
     color_pool.dedup();
-    rules
-        .rules
-        .retain(|key, _| color_pool.contains(&(*key as u8)));
+    rules.rules.retain(|key, _| color_pool.contains(key));
     rules.color_pool.clear();
-    for tile in &color_pool {
-        let tile = Tile::from_u8(*tile);
-        rules.rules.entry(tile).or_default();
+    for tile in color_pool {
+        let defaults = rules.defaults.get(&tile);
+        rules
+            .rules
+            .entry(tile)
+            .or_insert_with(|| defaults.cloned().unwrap_or_else(|| vec![Rule::default()]));
         rules.color_pool.push(Some(tile));
     }
     rules.color_pool.push(None);
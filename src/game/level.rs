@@ -2,12 +2,24 @@
 
 use std::any::TypeId;
 
-use bevy::ecs::component::HookContext;
+#[cfg(not(target_family = "wasm"))]
+use bevy::asset::LoadedFolder;
+use bevy::ecs::component::{ComponentHook, HookContext, Mutable, StorageType};
+use bevy::ecs::system::SystemParam;
 use bevy::ecs::world::DeferredWorld;
 use bevy::prelude::*;
-use bevy::reflect::TypePath;
 
-use super::logic::{GridIterations, IterationState, PlayerRules};
+use super::{
+    interface::Help,
+    logic::{GridIterations, IterationState, MAX_GENERATIONS, PlayerRules},
+    objective::Objective,
+    pack::LevelRegistry,
+    simulation::{
+        ANY, Boundary, Combinator, Count, Grid, HOLE, Layout, Locks, MAX_NEIGHBOURS, Radius, Rule,
+        RuleSet, Schedule, Tile, Topology, WALL,
+    },
+    undo::RuleHistory,
+};
 use crate::{asset_tracking::LoadResource, audio::music, screens::Screen, theme::palette::*};
 
 pub(super) fn plugin(app: &mut App) {
@@ -23,10 +35,13 @@ pub struct LevelAssets {
     pub font: Handle<Font>,
     #[dependency]
     pub music: Handle<AudioSource>,
+    /// The `assets/levels` folder, holding a [`LevelPack`](super::pack::LevelPack) per file.
+    #[cfg(not(target_family = "wasm"))]
     #[dependency]
-    pub puzzles: Handle<Levels>,
+    pub packs: Handle<LoadedFolder>,
+    #[cfg(target_family = "wasm")]
     #[dependency]
-    pub solutions: Handle<Levels>,
+    pub packs: Vec<Handle<super::pack::LevelPack>>,
     #[dependency]
     pub tilesheet: Handle<Image>,
     #[dependency]
@@ -56,8 +71,13 @@ impl FromWorld for LevelAssets {
             atlas,
             font: assets.load("fonts/JetBrainsMonoNerdFontPropo-Regular.ttf"),
             music: assets.load("audio/music/Cool-Puzzle-Groovin-2.ogg"),
-            puzzles: assets.load("levels/puzzles.ron"),
-            solutions: assets.load("levels/solutions.ron"),
+            #[cfg(not(target_family = "wasm"))]
+            packs: assets.load_folder("levels"),
+            #[cfg(target_family = "wasm")]
+            packs: super::pack::BUNDLED_PACKS
+                .iter()
+                .map(|path| assets.load(*path))
+                .collect(),
             tilesheet: assets.load("images/tilesheet.png"),
             help_general: assets.load("images/tutorial1.png"),
             help_winning: assets.load("images/tutorial1a.png"),
@@ -73,8 +93,7 @@ pub struct LevelEntity(pub Entity);
 pub fn spawn_level(
     mut commands: Commands,
     level_assets: Res<LevelAssets>,
-    levels: Res<Assets<Levels>>,
-    current_level: Res<State<Level>>,
+    current_level: CurrentLevel,
     mut grid_iter: ResMut<GridIterations>,
     mut state: ResMut<NextState<IterationState>>,
 ) {
@@ -92,56 +111,255 @@ pub fn spawn_level(
         .id();
     commands.insert_resource(LevelEntity(parent));
 
-    if let Some(level) = levels.get(level_assets.puzzles.id()) {
-        let grid = &level.levels[*current_level.get() as usize];
+    let mut max_rules = one_rule_per_color();
+    let mut defaults = RuleSet::new();
+    if let Some(level) = current_level.get() {
+        let board = &level.puzzle;
         grid_iter.grid.clear();
-        grid_iter.grid.push((*grid).to_vec());
-    }
-
-    if let Some(solution) = levels.get(level_assets.solutions.id()) {
-        let grid = &solution.levels[*current_level.get() as usize];
-        grid_iter.goal = (*grid).to_vec();
-        commands.spawn_batch(grid.render_solution(parent));
+        grid_iter.grid.push(board.tiles.clone());
+        grid_iter.goal = level.solution.clone();
+        grid_iter.objective = level.objective.clone();
+        grid_iter.layout = board.layout();
+        grid_iter.max = level.max.unwrap_or(MAX_GENERATIONS);
+        grid_iter.seed = board.seed.unwrap_or_else(rand::random);
+        defaults = board.preset_rules();
+        // Room for every preset, even past the level's usual allowance.
+        let presets = defaults.values().map(Vec::len).max().unwrap_or(0);
+        max_rules = board.rules_per_color.max(presets);
+        let boards = level.objective.boards(&level.solution, board.layout());
+        for (slot, (tiles, layout)) in boards.iter().enumerate() {
+            let slot = (slot, boards.len());
+            commands.spawn_batch(tiles.render_solution(*layout, parent, &level_assets, slot));
+        }
     }
-    commands.insert_resource(PlayerRules::default());
+    commands.insert_resource(PlayerRules {
+        max_rules,
+        rules: defaults.clone(),
+        defaults,
+        ..default()
+    });
+    commands.insert_resource(RuleHistory::default());
     state.set(IterationState::Reset);
 }
 
+/// A tile of the board being played, holding its index in the grid.
 #[derive(Component)]
-pub struct Puzzle;
+pub struct Puzzle(pub usize);
 #[derive(Component)]
 pub struct Solution;
-pub type Grid = Vec<u8>;
-#[derive(serde::Deserialize, Asset, TypePath)]
-pub struct Levels {
-    levels: Vec<Grid>,
+/// A level as written in its pack: what the player is told about it,
+/// the board they start from and the board they have to reach.
+#[derive(serde::Deserialize, Clone)]
+pub struct LevelData {
+    pub name: String,
+    pub tier: Tier,
+    /// Generations the player gets instead of [`MAX_GENERATIONS`].
+    #[serde(default)]
+    pub max: Option<usize>,
+    /// Steps the level's own solution takes.
+    #[serde(default)]
+    pub par: Option<usize>,
+    #[serde(default)]
+    pub hint: Option<String>,
+    /// Tutorial pages past the basics the level introduces, they stay on the
+    /// sidebar once it has been reached.
+    #[serde(default)]
+    pub help: Vec<Help>,
+    pub puzzle: Board,
+    /// Laid out like the puzzle, which it shares its size and layout with.
+    /// Cells holding [`ANY`] (255) can end up any color, and cells under a
+    /// wall or hole are never checked. Only needed when the objective refers to it.
+    #[serde(default)]
+    pub solution: Grid,
+    #[serde(default)]
+    pub objective: Objective,
+}
+#[derive(serde::Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Tier {
+    Beginner,
+    Intermediate,
+    Expert,
+}
+impl Tier {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tier::Beginner => "Beginner",
+            Tier::Intermediate => "Intermediate",
+            Tier::Expert => "Expert",
+        }
+    }
+}
+/// The level being played, as read from the [`LevelRegistry`].
+#[derive(SystemParam)]
+pub struct CurrentLevel<'w> {
+    registry: Res<'w, LevelRegistry>,
+    level: Res<'w, State<Level>>,
+}
+impl CurrentLevel<'_> {
+    pub fn get(&self) -> Option<&LevelData> {
+        self.registry.get(*self.level.get())
+    }
+}
+/// The board a level starts from.
+#[derive(serde::Deserialize, Clone)]
+pub struct Board {
+    pub width: usize,
+    pub height: usize,
+    #[serde(default)]
+    pub boundary: Boundary,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default)]
+    pub radius: Radius,
+    #[serde(default)]
+    pub schedule: Schedule,
+    #[serde(default = "one_rule_per_color")]
+    pub rules_per_color: usize,
+    /// Pins the seed rule chances are rolled from, otherwise every visit to
+    /// the level picks a new one.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Rules colors start the level with, in order.
+    #[serde(default)]
+    pub rules: Vec<PresetRule>,
+    /// What walls look like to the cells next to them.
+    #[serde(default)]
+    pub wall: Tile,
+    /// What holes look like to the cells next to them.
+    #[serde(default)]
+    pub hole: Tile,
+    /// The starting colors, with [`WALL`] (254) for cells that never change
+    /// and [`HOLE`] (253) for gaps in the board.
+    pub tiles: Grid,
+}
+/// A rule a level starts a color with, written like the rule editor shows it.
+/// Anything left out starts as it would on a new rule.
+#[derive(serde::Deserialize, Clone)]
+pub struct PresetRule {
+    /// The color whose rule it is.
+    pub color: Tile,
+    #[serde(default)]
+    pub tiles: [Option<Tile>; 2],
+    #[serde(default)]
+    pub combinator: Combinator,
+    #[serde(default)]
+    pub count: Count,
+    #[serde(default)]
+    pub invert: bool,
+    /// Entries in [`Rule::mask`] order, those past the end stay on.
+    #[serde(default)]
+    pub mask: Vec<bool>,
+    #[serde(default)]
+    pub result: Option<Tile>,
+    #[serde(default = "always")]
+    pub chance: u8,
+    #[serde(default)]
+    pub locked: Locks,
+}
+fn always() -> u8 {
+    Rule::ALWAYS
+}
+impl PresetRule {
+    pub fn rule(&self) -> Rule {
+        let mut mask = [true; MAX_NEIGHBOURS];
+        for (enabled, &preset) in mask.iter_mut().zip(&self.mask) {
+            *enabled = preset;
+        }
+        Rule {
+            tiles: self.tiles,
+            combinator: self.combinator,
+            count: self.count,
+            invert: self.invert,
+            mask,
+            result: self.result,
+            chance: self.chance,
+            locked: self.locked,
+            ..Rule::default()
+        }
+    }
+}
+fn one_rule_per_color() -> usize {
+    1
+}
+impl Board {
+    pub fn layout(&self) -> Layout {
+        Layout::new(self.width, self.height)
+            .with_boundary(self.boundary)
+            .with_topology(self.topology)
+            .with_radius(self.radius)
+            .with_schedule(self.schedule)
+            .with_walls(self.wall, self.hole)
+    }
+    /// The preset rules of each color, in the order the level lists them.
+    pub fn preset_rules(&self) -> RuleSet {
+        let mut rules = RuleSet::new();
+        for preset in &self.rules {
+            let list = rules.entry(preset.color).or_default();
+            list.push(Rule {
+                preset: Some(list.len()),
+                ..preset.rule()
+            });
+        }
+        rules
+    }
 }
 pub trait Utility {
-    fn render_puzzle(&self, parent: Entity) -> (Vec<impl Bundle>, f32);
-    fn render_solution(&self, parent: Entity) -> Vec<(Solution, ChildOf, Transform, Sprite)>;
-    fn check_neighbours(&self, index: usize, input: &PlayerRules) -> Option<Tile>;
+    /// Places the cells of a board, leaving out its holes. The caller gives
+    /// each one the [`Tile`] or [`Wall`] it shows.
+    fn render_puzzle(
+        &self,
+        layout: Layout,
+        parent: Entity,
+    ) -> (Vec<(Puzzle, ChildOf, Transform)>, f32);
+    /// Draws a goal board into the preview, which `slot` shares with as many
+    /// boards as it counts, top to bottom. Cells any color satisfies are drawn
+    /// with the hatched tile from `assets`, walls and holes like on the board.
+    fn render_solution(
+        &self,
+        layout: Layout,
+        parent: Entity,
+        assets: &LevelAssets,
+        slot: (usize, usize),
+    ) -> Vec<(Solution, ChildOf, Transform, Sprite)>;
 }
+/// The number of tiles the longer side of a board is scaled to fit.
+pub const MAX_GRID_SIZE: usize = 16;
 const TILE_SIZE: f32 = 120.;
 const MINI_SCALE: f32 = 2.5;
-pub const PADDING: f32 = 8.;
-impl Utility for Grid {
-    fn render_puzzle(&self, parent: Entity) -> (Vec<impl Bundle>, f32) {
-        let grid_size = self.len().isqrt();
-        let tile_size = TILE_SIZE * (16 / grid_size) as f32;
-        let offset = tile_size * grid_size as f32 / 2. - tile_size / 2.;
-        let mut coords = Vec2::splat(-offset);
+/// Where the goal preview sits, and the side of the square it fills.
+const PREVIEW_CENTER: Vec2 = Vec2::new(TILE_SIZE * 30. / MINI_SCALE, TILE_SIZE * 10. / MINI_SCALE);
+const PREVIEW_SIZE: f32 = TILE_SIZE / MINI_SCALE * MAX_GRID_SIZE as f32;
+const PADDING: f32 = 8.;
+/// The side of a tile drawn in a cell `tile_size` across, leaving a gap to
+/// its neighbours that narrows with the tiles of very large boards.
+pub fn padded(tile_size: f32) -> f32 {
+    tile_size - PADDING.min(tile_size / 4.)
+}
+/// The dark block at the end of the tilesheet.
+const WALL_SPRITE: usize = 17;
+impl Utility for [u8] {
+    fn render_puzzle(
+        &self,
+        layout: Layout,
+        parent: Entity,
+    ) -> (Vec<(Puzzle, ChildOf, Transform)>, f32) {
+        let grid_size = layout.width.max(layout.height);
+        let tile_size = TILE_SIZE * MAX_GRID_SIZE as f32 / grid_size as f32;
+        let offset = board_offset(layout, tile_size);
+        let mut coords = -offset;
         let mut tiles = vec![];
         for (i, tile) in self.iter().enumerate() {
-            if i > 0 && i % grid_size == 0 {
+            if i > 0 && i % layout.width == 0 {
                 coords.y += tile_size;
-                coords.x = -offset;
+                coords.x = -offset.x + row_shift(layout, i / layout.width, tile_size);
             } else if i > 0 {
                 coords.x += tile_size;
             }
-            let tile = Tile::from_u8(*tile);
+            if *tile == HOLE {
+                continue;
+            }
             tiles.push((
-                tile,
-                Puzzle,
+                Puzzle(i),
                 ChildOf(parent),
                 Transform::from_translation(coords.extend(0.0)),
             ));
@@ -149,127 +367,89 @@ impl Utility for Grid {
         (tiles, tile_size)
     }
 
-    fn render_solution(&self, parent: Entity) -> Vec<(Solution, ChildOf, Transform, Sprite)> {
-        let grid_size = self.len().isqrt();
-        let tile_size = TILE_SIZE / MINI_SCALE * (16 / grid_size) as f32;
-        let offset = tile_size * grid_size as f32 / 2. - tile_size / 2.;
-        let mut coords = Vec2::splat(-offset);
+    fn render_solution(
+        &self,
+        layout: Layout,
+        parent: Entity,
+        assets: &LevelAssets,
+        (slot, slots): (usize, usize),
+    ) -> Vec<(Solution, ChildOf, Transform, Sprite)> {
+        let grid_size = layout.width.max(layout.height);
+        let slots = slots.max(1) as f32;
+        let tile_size = TILE_SIZE / MINI_SCALE * MAX_GRID_SIZE as f32 / grid_size as f32 / slots;
+        let center = PREVIEW_CENTER
+            + Vec2::Y * (PREVIEW_SIZE / 2. - (slot as f32 + 0.5) * PREVIEW_SIZE / slots);
+        let offset = board_offset(layout, tile_size);
+        let mut coords = -offset;
         let mut tiles = vec![];
         for (i, tile) in self.iter().enumerate() {
-            if i > 0 && i % grid_size == 0 {
+            if i > 0 && i % layout.width == 0 {
                 coords.y += tile_size;
-                coords.x = -offset;
+                coords.x = -offset.x + row_shift(layout, i / layout.width, tile_size);
             } else if i > 0 {
                 coords.x += tile_size;
             }
-            let tile = Tile::from_u8(*tile);
+            let size = Vec2::splat(padded(tile_size));
+            let from_sheet = |index| Sprite {
+                image: assets.tilesheet.clone(),
+                custom_size: Some(size),
+                texture_atlas: Some(TextureAtlas {
+                    layout: assets.atlas.clone(),
+                    index,
+                }),
+                ..default()
+            };
+            let sprite = match *tile {
+                ANY => from_sheet(Tile::Empty as usize),
+                WALL => from_sheet(WALL_SPRITE),
+                HOLE => continue,
+                tile => Sprite::from_color(Tile::from_u8(tile).color(), size),
+            };
             tiles.push((
                 Solution,
                 ChildOf(parent),
-                Transform::from_translation(
-                    (TILE_SIZE * Vec2::new(30., 10.) / MINI_SCALE + coords).extend(0.0),
-                ),
-                Sprite::from_color(tile.color(), Vec2::splat(tile_size - PADDING)),
+                Transform::from_translation((center + coords).extend(0.0)),
+                sprite,
             ));
         }
         tiles
     }
-
-    fn check_neighbours(&self, index: usize, rules: &PlayerRules) -> Option<Tile> {
-        // probably inefficient
-        let gs = self.len().isqrt() as i32;
-        let offsets = &[-(gs + 1), -gs, -(gs - 1), -1, 1, gs - 1, gs, gs + 1];
-        let left_offset = &[-(gs + 1), -1, gs - 1];
-        let right_offset = &[-(gs - 1), 1, gs + 1];
-        let left_edge = index % gs as usize == 0;
-        let right_edge = (index + 1) % gs as usize == 0;
-        if let Some(rule) = &rules.rules.get(&Tile::from_u8(self[index])) {
-            let neighbours: Vec<Tile> = offsets
-                .iter()
-                .enumerate()
-                .filter(|&(i, _)| rule.mask[i])
-                .map(|(_, &offset)| {
-                    let neighbor = index as i32 + offset;
-                    if neighbor >= 0 && neighbor < self.len() as i32 {
-                        if (left_edge && left_offset.contains(&offset))
-                            || (right_edge && right_offset.contains(&offset))
-                        {
-                            Tile::Empty
-                        } else {
-                            Tile::from_u8(self[neighbor as usize])
-                        }
-                    } else {
-                        Tile::Empty
-                    }
-                })
-                .collect();
-            rule.tiles
-                .iter()
-                .flatten()
-                .any(|tile| neighbours.contains(tile) ^ rule.invert)
-                .then_some(rule.result)
-                .flatten()
-        } else {
-            None
-        }
+}
+/// Distance from the center of the board to the center of its first tile.
+fn board_offset(layout: Layout, tile_size: f32) -> Vec2 {
+    let width = layout.width as f32 + row_shift(layout, 1, 1.);
+    Vec2::new(width, layout.height as f32) * tile_size / 2. - tile_size / 2.
+}
+/// Hex boards draw their odd rows half a tile to the right, so the tiles of
+/// neighbouring rows interlock like the cells they stand for.
+fn row_shift(layout: Layout, row: usize, tile_size: f32) -> f32 {
+    match layout.topology {
+        Topology::Hex if row % 2 == 1 && layout.height > 1 => tile_size / 2.,
+        _ => 0.,
     }
 }
 
+/// The level being played, as its place in the [`LevelRegistry`].
 #[derive(States, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug, Default)]
 #[states(scoped_entities)]
-pub enum Level {
-    #[default]
-    Beginner1,
-    Beginner2,
-    Beginner3,
-    Intermediate1,
-    Intermediate2,
-    Intermediate3,
-    Intermediate4,
-    Intermediate5,
-    Expert1,
-    Expert2,
-    Expert3,
-    Expert4,
+pub struct Level {
+    pub pack: usize,
+    pub index: usize,
 }
 
-pub trait Switch {
-    fn next(&self) -> Self;
-}
+// `Tile` lives in the Bevy-free simulation module, so its component impl is spelled out here.
+impl Component for Tile {
+    const STORAGE_TYPE: StorageType = StorageType::Table;
+    type Mutability = Mutable;
 
-impl Switch for Level {
-    fn next(&self) -> Self {
-        match self {
-            Level::Beginner1 => Level::Beginner2,
-            Level::Beginner2 => Level::Beginner3,
-            Level::Beginner3 => Level::Intermediate1,
-            Level::Intermediate1 => Level::Intermediate2,
-            Level::Intermediate2 => Level::Intermediate3,
-            Level::Intermediate3 => Level::Intermediate4,
-            Level::Intermediate4 => Level::Intermediate5,
-            Level::Intermediate5 => Level::Expert1,
-            Level::Expert1 => Level::Expert2,
-            Level::Expert2 => Level::Expert3,
-            Level::Expert3 => Level::Expert4,
-            Level::Expert4 => Level::Beginner1,
-        }
+    fn on_insert() -> Option<ComponentHook> {
+        Some(insert_sprite::<Tile>)
     }
 }
-
-#[derive(Component, Default, Debug, Copy, Clone, Eq, Hash, PartialEq, PartialOrd, Ord)]
-#[component(on_insert = insert_sprite::<Tile>)]
-pub enum Tile {
-    Red,
-    Green,
-    Blue,
-    Yellow,
-    Orange,
-    Purple,
-    Brown,
-    Pink,
-    #[default]
-    Empty,
-}
+/// A board cell no rule changes.
+#[derive(Component)]
+#[component(on_insert = insert_sprite::<Wall>)]
+pub struct Wall;
 #[derive(Component, Default, Copy, Clone, Eq, Hash, PartialEq)]
 #[component(on_insert = insert_sprite::<Face>)]
 pub enum Face {
@@ -288,11 +468,11 @@ fn insert_sprite<T: Component>(mut world: DeferredWorld, context: HookContext) {
     } else if TypeId::of::<T>() == TypeId::of::<Face>() {
         9 + *world.get::<Face>(context.entity).unwrap_or(&Face::Thinking) as usize
     } else {
-        17 // last tile
+        WALL_SPRITE
     };
     world.commands().entity(context.entity).insert(Sprite {
         image,
-        custom_size: Some(Vec2::splat(tile_size - PADDING)),
+        custom_size: Some(Vec2::splat(padded(tile_size))),
         texture_atlas: Some(TextureAtlas {
             layout: atlas,
             index: tile,
@@ -301,19 +481,6 @@ fn insert_sprite<T: Component>(mut world: DeferredWorld, context: HookContext) {
     });
 }
 impl Tile {
-    pub fn from_u8(value: u8) -> Tile {
-        match value {
-            0 => Tile::Red,
-            1 => Tile::Green,
-            2 => Tile::Blue,
-            3 => Tile::Yellow,
-            4 => Tile::Orange,
-            5 => Tile::Purple,
-            6 => Tile::Brown,
-            7 => Tile::Pink,
-            _ => Tile::Empty,
-        }
-    }
     pub fn color(&self) -> Color {
         match self {
             Tile::Red => RED,
@@ -327,17 +494,4 @@ impl Tile {
             Tile::Empty => EMPTY,
         }
     }
-    pub fn all() -> [Tile; 9] {
-        [
-            Tile::Red,
-            Tile::Green,
-            Tile::Blue,
-            Tile::Yellow,
-            Tile::Orange,
-            Tile::Purple,
-            Tile::Brown,
-            Tile::Pink,
-            Tile::Empty,
-        ]
-    }
 }
@@ -1,10 +1,18 @@
-use std::time::Duration;
+use std::{mem, time::Duration};
 
-use bevy::{platform::collections::HashMap, prelude::*, time::common_conditions::on_timer};
+use bevy::{
+    prelude::*,
+    tasks::{ComputeTaskPool, Task, futures::check_ready},
+    time::common_conditions::on_timer,
+};
+use rand::{SeedableRng, rngs::StdRng};
 
 use super::{
     animation::AnimationConfig,
-    level::{Face, Grid, LevelAssets, LevelEntity, PADDING, Puzzle, Tile, Utility},
+    bitplane,
+    level::{Face, LevelAssets, LevelEntity, Puzzle, Utility, Wall, padded},
+    objective::Objective,
+    simulation::{self, ANY, Grid, HOLE, Layout, Recurrence, RuleSet, Settled, Tile, WALL},
 };
 use crate::{menus::Menu, screens::Screen, theme::shader::CustomMaterial};
 
@@ -12,11 +20,9 @@ pub(super) fn plugin(app: &mut App) {
     app.init_state::<IterationState>();
     app.init_resource::<PlayerRules>();
     app.init_resource::<GridIterations>();
+    app.init_resource::<ParallelThreshold>();
     app.add_systems(OnEnter(IterationState::Reset), reset_step);
-    app.add_systems(
-        OnEnter(IterationState::Displaying),
-        (clear_board, rendering_step).chain(),
-    );
+    app.add_systems(OnEnter(IterationState::Displaying), rendering_step);
     app.add_systems(
         OnExit(IterationState::Displaying),
         (rendering_step, check_wincon).chain(),
@@ -25,12 +31,13 @@ pub(super) fn plugin(app: &mut App) {
         OnEnter(IterationState::Simulating),
         simulation_step.run_if(not(resource_exists::<Victory>)),
     );
+    app.add_systems(Update, finish_step.run_if(resource_exists::<PendingStep>));
     app.add_systems(
         Update,
         simulation_system.run_if(
             resource_exists::<AutomaticSimulation>
                 .and(on_timer(Duration::from_secs_f32(ANIMATION_DURATION)))
-                .and(in_state(Screen::Gameplay))
+                .and(in_state(Screen::Gameplay).or(in_state(Screen::Sandbox)))
                 .and(in_state(Menu::None)),
         ),
     );
@@ -38,46 +45,186 @@ pub(super) fn plugin(app: &mut App) {
 pub const ANIMATION_DURATION: f32 = 0.6;
 #[derive(Resource, Default, Debug)]
 pub struct PlayerRules {
-    pub rules: HashMap<Tile, Rule>,
+    pub rules: RuleSet,
     pub color_pool: Vec<Option<Tile>>,
+    /// How many rules each color may have in the current level.
+    pub max_rules: usize,
+    /// The rules the level starts each color with, which resetting a rule
+    /// goes back to. Colors without any start with a single blank rule.
+    pub defaults: RuleSet,
 }
-#[derive(Clone, Debug)]
-pub struct Rule {
-    pub tiles: [Option<Tile>; 2],
-    pub invert: bool,
-    pub mask: [bool; 8],
-    pub changed: [bool; 8],
-    pub result: Option<Tile>,
+/// Boards with at least this many cells are stepped in row bands on a task of
+/// the [`ComputeTaskPool`], when their rules allow it.
+#[derive(Resource, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ParallelThreshold(pub usize);
+impl ParallelThreshold {
+    /// The thresholds the settings menu steps through, from always to never.
+    /// Levels go up to 16x16, the sandbox up to 256x256.
+    pub const CHOICES: [usize; 8] = [
+        0,
+        8 * 8,
+        16 * 16,
+        32 * 32,
+        64 * 64,
+        128 * 128,
+        256 * 256,
+        usize::MAX,
+    ];
 }
-impl Default for Rule {
+impl Default for ParallelThreshold {
     fn default() -> Self {
-        Self {
-            tiles: [None, None],
-            invert: false,
-            mask: [true; 8],
-            changed: [false; 8],
-            result: None,
-        }
+        Self(64 * 64)
     }
 }
+/// Generations a run gets unless the level sets its own cap.
+pub const MAX_GENERATIONS: usize = 50;
 #[derive(Resource)]
 pub struct GridIterations {
     pub grid: Vec<Grid>,
     pub goal: Grid,
+    pub objective: Objective,
+    pub layout: Layout,
     pub max: usize,
+    /// Seeds `rng` on every reset, so each run of a level rolls the same chances.
+    pub seed: u64,
+    pub rng: StdRng,
+    /// The generations of this run so far, to notice when a board comes back.
+    pub recurrence: Recurrence,
+    /// How the run settled, once a board has come back.
+    pub settled: Option<Settled>,
+    /// Boards stepped back past, the next one last, each with `rng` as
+    /// stepping to it left it.
+    pub rewound: Vec<(Grid, StdRng)>,
+    /// `rng` as it was before each step in `grid`, so stepping back rolls the
+    /// same chances on the way forward again.
+    pub rolled: Vec<StdRng>,
+    /// How the board on show was reached, which decides how it is drawn in.
+    pub playback: Playback,
+}
+/// Which way the run last moved through its history.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Playback {
+    /// A step forward, dissolving the tiles that changed.
+    #[default]
+    Forward,
+    /// A step back, with the dissolve played in reverse.
+    Back,
+    /// Straight to a board further away, without any dissolve.
+    Jump,
 }
 impl Default for GridIterations {
     fn default() -> Self {
         Self {
             grid: vec![],
             goal: vec![],
-            max: 50,
+            objective: Objective::default(),
+            layout: Layout::default(),
+            max: MAX_GENERATIONS,
+            seed: 0,
+            rng: StdRng::seed_from_u64(0),
+            recurrence: Recurrence::default(),
+            settled: None,
+            rewound: vec![],
+            rolled: vec![],
+            playback: Playback::default(),
         }
     }
 }
 impl GridIterations {
     pub fn is_correct(&self, index: usize) -> bool {
-        self.grid.last().unwrap()[index] == self.goal[index]
+        simulation::matches(self.grid.last().unwrap()[index], self.goal[index])
+    }
+    /// How many boards each generation adds to `grid`, one per phase of the schedule.
+    pub fn phases(&self) -> usize {
+        self.layout.schedule.phases(self.layout)
+    }
+    /// Generations in `grid` so far, counting the starting board and any
+    /// generation still part way through its phases.
+    pub fn generations(&self) -> usize {
+        (self.grid.len() - 1).div_ceil(self.phases()) + 1
+    }
+    /// Whether the last board is part way through a generation.
+    pub fn mid_generation(&self) -> bool {
+        !(self.grid.len() - 1).is_multiple_of(self.phases())
+    }
+    /// Whether the boards up to the one on show reach the objective.
+    pub fn won(&self) -> bool {
+        if self.mid_generation() {
+            return false;
+        }
+        let generations = self.grid.iter().step_by(self.phases()).map(Vec::as_slice);
+        self.objective
+            .achieved(generations, &self.goal, self.layout.width)
+    }
+    /// Where the board on show sits in the run's history.
+    pub fn shown(&self) -> usize {
+        self.grid.len() - 1
+    }
+    /// Boards in the run's history, counting those stepped back past.
+    pub fn history_len(&self) -> usize {
+        self.grid.len() + self.rewound.len()
+    }
+    /// Shows board `index` of the history, stepping back or forward through it
+    /// without simulating anything.
+    pub fn jump_to(&mut self, index: usize, rules: &RuleSet) {
+        let index = index.min(self.history_len() - 1);
+        while self.grid.len() > index + 1 {
+            let board = self.grid.pop().unwrap();
+            let rng = mem::replace(&mut self.rng, self.rolled.pop().unwrap());
+            self.rewound.push((board, rng));
+        }
+        while self.grid.len() < index + 1 {
+            let (board, rng) = self.rewound.pop().unwrap();
+            self.rolled.push(mem::replace(&mut self.rng, rng));
+            self.grid.push(board);
+        }
+        self.resettle(rules);
+    }
+    /// Adds `board`, one phase on from the last board, and `rng` as stepping
+    /// to it left the rolls.
+    fn push_step(&mut self, board: Grid, rng: StdRng, rules: &RuleSet) {
+        // Stepping on from a rewound board replaces the boards after it.
+        self.rewound.clear();
+        self.playback = Playback::Forward;
+        self.rolled.push(mem::replace(&mut self.rng, rng));
+        let phases = self.phases();
+        if self.grid.len() == 1 {
+            // The starting board can be edited up to the first step.
+            self.recurrence.clear();
+            let history = &self.grid;
+            self.recurrence
+                .record(0, &history[0], |g| &history[g * phases]);
+        }
+        self.grid.push(board);
+        // With chances in play a board coming back doesn't mean the run repeats.
+        if self.settled.is_none() && !self.mid_generation() && !simulation::rolls(rules) {
+            let generation = self.generations() - 1;
+            let history = &self.grid;
+            self.settled = self
+                .recurrence
+                .record(generation, history.last().unwrap(), |g| {
+                    &history[g * phases]
+                });
+        }
+    }
+    /// Goes over the boards up to the one on show again, to tell how the run
+    /// had settled by then.
+    fn resettle(&mut self, rules: &RuleSet) {
+        self.recurrence.clear();
+        self.settled = None;
+        if simulation::rolls(rules) {
+            return;
+        }
+        let phases = self.phases();
+        let history = &self.grid;
+        for (generation, board) in history.iter().step_by(phases).enumerate() {
+            self.settled = self
+                .recurrence
+                .record(generation, board, |g| &history[g * phases]);
+            if self.settled.is_some() {
+                break;
+            }
+        }
     }
 }
 #[derive(Resource)]
@@ -90,7 +237,7 @@ fn simulation_system(
     grid: Res<GridIterations>,
     mut state: ResMut<NextState<IterationState>>,
 ) {
-    if grid.grid.len() >= grid.max {
+    if grid.settled.is_some() || (grid.generations() >= grid.max && !grid.mid_generation()) {
         commands.remove_resource::<AutomaticSimulation>();
         return;
     }
@@ -106,84 +253,227 @@ pub fn step_through(
     commands.insert_resource(DisableControls);
     state.set(IterationState::Simulating);
 }
+/// Shows the board before the one on show, keeping the later ones to scrub
+/// forward to.
+pub fn step_back(
+    _: Trigger<Pointer<Click>>,
+    mut commands: Commands,
+    rules: Res<PlayerRules>,
+    mut grid: ResMut<GridIterations>,
+    mut state: ResMut<NextState<IterationState>>,
+) {
+    commands.remove_resource::<AutomaticSimulation>();
+    let Some(previous) = grid.shown().checked_sub(1) else {
+        return;
+    };
+    grid.jump_to(previous, &rules.rules);
+    grid.playback = Playback::Back;
+    after_jump(&mut commands, &grid);
+    state.set(IterationState::Displaying);
+}
+/// Takes back a win the board on show comes before, so the run can step on
+/// from it again. The rules can be edited again only back at the start.
+pub fn after_jump(commands: &mut Commands, grid: &GridIterations) {
+    if !grid.won() {
+        commands.remove_resource::<Victory>();
+    }
+    if grid.shown() == 0 {
+        commands.remove_resource::<DisableControls>();
+    } else {
+        commands.insert_resource(DisableControls);
+    }
+}
 pub fn reset_simulation(_: Trigger<Pointer<Click>>, mut state: ResMut<NextState<IterationState>>) {
     state.set(IterationState::Reset);
 }
 
+/// A step running in bands on the [`ComputeTaskPool`], which [`finish_step`]
+/// picks up once it is done so the frame never waits on it.
+#[derive(Resource)]
+struct PendingStep(Task<Grid>);
+
 fn simulation_step(
+    mut commands: Commands,
     input: Res<PlayerRules>,
     mut grid: ResMut<GridIterations>,
+    threshold: Res<ParallelThreshold>,
     mut state: ResMut<NextState<IterationState>>,
 ) {
-    let current_grid = grid.grid.last().unwrap();
-    let mut new_grid = current_grid.clone();
-    for (i, new_tile) in new_grid.iter_mut().enumerate().take(current_grid.len()) {
-        if let Some(tile) = current_grid.check_neighbours(i, &input) {
-            *new_tile = tile as u8;
-        }
+    let phase = (grid.grid.len() - 1) % grid.phases();
+    let last = grid.grid.last().unwrap();
+    if grid.layout.len() >= threshold.0 && bitplane::supports(grid.layout, &input.rules) {
+        let task = spawn_in_bands(last, grid.layout, &input.rules, phase);
+        commands.insert_resource(PendingStep(task));
+        return;
     }
-    grid.grid.push(new_grid);
+    let mut rng = grid.rng.clone();
+    let new_grid = simulation::step_phase(last, grid.layout, &input.rules, phase, &mut rng);
+    grid.push_step(new_grid, rng, &input.rules);
     state.set(IterationState::Displaying);
 }
-fn clear_board(mut commands: Commands, board: Query<Entity, With<Puzzle>>) {
-    for entity in &board {
-        commands.entity(entity).despawn();
+/// Shows the board of a [`PendingStep`] once its bands are all stepped. A step
+/// the run has moved on from in the meantime, by a reset or a step back, is
+/// dropped instead.
+fn finish_step(
+    mut commands: Commands,
+    mut pending: ResMut<PendingStep>,
+    input: Res<PlayerRules>,
+    mut grid: ResMut<GridIterations>,
+    current: Res<State<IterationState>>,
+    mut state: ResMut<NextState<IterationState>>,
+) {
+    if *current.get() != IterationState::Simulating {
+        commands.remove_resource::<PendingStep>();
+        return;
     }
+    let Some(new_grid) = check_ready(&mut pending.0) else {
+        return;
+    };
+    commands.remove_resource::<PendingStep>();
+    // Boards stepped in bands never roll, so the rng stays as it was.
+    let rng = grid.rng.clone();
+    grid.push_step(new_grid, rng, &input.rules);
+    state.set(IterationState::Displaying);
 }
+/// Steps a band of rows on each thread the [`ComputeTaskPool`] has, in a task
+/// of its own so the frame never waits on it.
+fn spawn_in_bands(grid: &[u8], layout: Layout, rules: &RuleSet, phase: usize) -> Task<Grid> {
+    let pool = ComputeTaskPool::get();
+    let count = pool.thread_num();
+    let (grid, rules) = (grid.to_vec(), rules.clone());
+    pool.spawn(async move { bitplane::step_in_bands(&grid, layout, &rules, phase, count) })
+}
+/// Draws the board on show. Tiles already on the board are updated where they
+/// differ, so a step only touches the cells it changed. A board that doesn't
+/// line up with the tiles, like a new level or a resized sandbox, is drawn
+/// from scratch.
 fn rendering_step(
     mut commands: Commands,
     mut level_assets: ResMut<LevelAssets>,
     level_entity: Res<LevelEntity>,
     grid: Res<GridIterations>,
     state: Res<State<IterationState>>,
+    board: Query<(Entity, &Puzzle, &ChildOf, Option<&Tile>, Option<&Children>)>,
+    faces: Query<&Face>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<CustomMaterial>>,
     time: Res<Time>,
 ) {
     let current = grid.grid.last().unwrap();
-    let previous = grid.grid.get(grid.grid.len().saturating_sub(2)).unwrap();
+    // The board on show before this one, dissolving into it.
+    let previous = match grid.playback {
+        Playback::Forward => grid.grid.get(grid.grid.len().saturating_sub(2)),
+        Playback::Back => grid.rewound.last().map(|(board, _)| board),
+        Playback::Jump => None,
+    };
+    let reverse = grid.playback == Playback::Back;
 
-    let (puzzle, tile_size) = current.render_puzzle(level_entity.0);
-    level_assets.tile_size = tile_size;
+    // Each cell's tile and the color it shows, walls and holes have none.
+    let mut drawn = vec![None; current.len()];
+    let mut lined_up = true;
+    for (entity, &Puzzle(i), parent, shown, children) in &board {
+        match drawn.get_mut(i) {
+            Some(cell @ None) if parent.0 == level_entity.0 && current[i] != HOLE => {
+                if shown.is_some() != (current[i] != WALL) {
+                    lined_up = false;
+                }
+                *cell = shown.map(|&shown| (entity, Some(shown), children));
+            }
+            _ => lined_up = false,
+        }
+    }
+    let tiles = current.iter().filter(|&&value| value != HOLE).count();
+    if board.iter().len() != tiles {
+        lined_up = false;
+    }
+    if !lined_up {
+        for (entity, ..) in &board {
+            commands.entity(entity).despawn();
+        }
+        drawn.fill(None);
+        let (puzzle, tile_size) = current.render_puzzle(grid.layout, level_entity.0);
+        level_assets.tile_size = tile_size;
+        for bundle in puzzle {
+            let i = bundle.0.0;
+            if current[i] == WALL {
+                commands.spawn((bundle, Wall));
+                continue;
+            }
+            let tile = commands.spawn(bundle).id();
+            #[cfg(feature = "dev")]
+            commands
+                .entity(tile)
+                .insert(Pickable::default())
+                .observe(crate::dev_tools::handle_debug_editor)
+                .observe(crate::dev_tools::handle_debug_painter);
+            drawn[i] = Some((tile, None, None));
+        }
+    }
 
     let mesh = meshes.add(Rectangle::default());
 
-    for (i, bundle) in puzzle.into_iter().enumerate() {
-        let tile = commands.spawn(bundle).id();
-
-        #[cfg(feature = "dev")]
-        commands
-            .entity(tile)
-            .insert(crate::dev_tools::EditorTileColor {
-                index: i,
-                color: Some(Tile::from_u8(current[i])),
-            })
-            .insert(Pickable::default())
-            .observe(crate::dev_tools::handle_debug_editor)
-            .observe(crate::dev_tools::handle_debug_painter);
-        commands.spawn((
-            ChildOf(tile),
-            if grid.is_correct(i) {
+    for (i, cell) in drawn.into_iter().enumerate() {
+        let Some((tile, shown, children)) = cell else {
+            continue;
+        };
+        let changed = previous.filter(|previous| {
+            previous[i] != current[i] && *state.get() == IterationState::Displaying
+        });
+        // Played in reverse, the tile stepped back to forms over the one
+        // stepped back from, instead of the old one burning away over the new.
+        let (under, over) = match changed {
+            Some(previous) if reverse => (previous[i], Some(current[i])),
+            Some(previous) => (current[i], Some(previous[i])),
+            None => (current[i], None),
+        };
+        if shown != Some(Tile::from_u8(under)) {
+            commands.entity(tile).insert(Tile::from_u8(under));
+            #[cfg(feature = "dev")]
+            commands
+                .entity(tile)
+                .insert(crate::dev_tools::EditorTileColor {
+                    index: i,
+                    color: Some(Tile::from_u8(current[i])),
+                });
+        }
+        // Sandbox boards have no goal to compare against, other objectives
+        // aren't about single tiles, and cells any color satisfies have no
+        // opinion either.
+        if grid.objective.is_solution() && grid.goal.get(i).is_some_and(|&goal| goal != ANY) {
+            let face = if grid.is_correct(i) {
                 Face::Happy
             } else {
                 Face::Sad
-            },
-            Transform::from_xyz(0.0, 0.0, 0.2),
-        ));
-        let material = materials.add(CustomMaterial {
-            sprite_texture: Some(level_assets.tilesheet.clone()),
-            params: Vec4::new(previous[i] as f32, 1.0, 0.04, time.elapsed_secs()),
-            burn_color: LinearRgba::from(Tile::from_u8(previous[i]).color()),
-        });
-        if current[i] != previous[i] && *state.get() == IterationState::Displaying {
+            };
+            let shown = children
+                .into_iter()
+                .flatten()
+                .find_map(|&child| Some((child, *faces.get(child).ok()?)));
+            match shown {
+                Some((_, shown)) if shown == face => {}
+                Some((child, _)) => {
+                    commands.entity(child).insert(face);
+                }
+                None => {
+                    commands.spawn((ChildOf(tile), face, Transform::from_xyz(0.0, 0.0, 0.2)));
+                }
+            }
+        }
+        if let Some(over) = over {
+            let dissolved = if reverse { 0.0 } else { 1.0 };
+            let material = materials.add(CustomMaterial {
+                sprite_texture: Some(level_assets.tilesheet.clone()),
+                params: Vec4::new(over as f32, dissolved, 0.04, time.elapsed_secs()),
+                burn_color: LinearRgba::from(Tile::from_u8(over).color()),
+            });
             commands.spawn((
                 ChildOf(tile),
                 StateScoped(IterationState::Displaying),
                 Mesh2d(mesh.clone()),
                 MeshMaterial2d(material.clone()),
-                AnimationConfig::new(material.clone(), 60),
+                AnimationConfig::new(material.clone(), 60, reverse),
                 Transform::default()
-                    .with_scale(Vec3::splat(level_assets.tile_size - PADDING))
+                    .with_scale(Vec3::splat(padded(level_assets.tile_size)))
                     .with_translation(Vec3::new(0.0, 0.0, 0.1)),
             ));
         }
@@ -195,13 +485,19 @@ fn reset_step(
     mut state: ResMut<NextState<IterationState>>,
 ) {
     grid.grid.truncate(1);
+    grid.rewound.clear();
+    grid.rolled.clear();
+    grid.playback = Playback::Forward;
+    grid.rng = StdRng::seed_from_u64(grid.seed);
+    grid.settled = None;
     commands.remove_resource::<AutomaticSimulation>();
     commands.remove_resource::<DisableControls>();
     commands.remove_resource::<Victory>();
+    commands.remove_resource::<PendingStep>();
     state.set(IterationState::Displaying);
 }
 fn check_wincon(mut commands: Commands, grid: Res<GridIterations>) {
-    if grid.grid.last().unwrap_or(&Vec::new()) == &grid.goal {
+    if grid.won() {
         commands.insert_resource(Victory);
         commands.remove_resource::<AutomaticSimulation>();
     }
@@ -230,3 +526,50 @@ pub enum IterationState {
     Displaying,
     Reset,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::simulation::{Count, Rule};
+
+    const R: u8 = Tile::Red as u8;
+    const G: u8 = Tile::Green as u8;
+
+    #[test]
+    fn rewinding_takes_back_the_win() {
+        // Green spreads a cell each step, filling the row on the second.
+        let rules = RuleSet::from([(
+            Tile::Red,
+            vec![Rule {
+                tiles: [Some(Tile::Green), None],
+                count: Count::at_least(1),
+                result: Some(Tile::Green),
+                ..default()
+            }],
+        )]);
+        let mut grid = GridIterations {
+            grid: vec![vec![G, R, R]],
+            goal: vec![G; 3],
+            layout: Layout::new(3, 1),
+            ..default()
+        };
+        let step = |grid: &mut GridIterations| {
+            let board = simulation::step(grid.grid.last().unwrap(), grid.layout, &rules);
+            let rng = grid.rng.clone();
+            grid.push_step(board, rng, &rules);
+        };
+        step(&mut grid);
+        assert!(!grid.won());
+        step(&mut grid);
+        assert!(grid.won());
+
+        grid.jump_to(1, &rules);
+        assert!(!grid.won());
+        assert_eq!(grid.history_len(), 3);
+
+        step(&mut grid);
+        assert!(grid.won());
+        assert_eq!(grid.shown(), 2);
+        assert!(grid.rewound.is_empty());
+    }
+}
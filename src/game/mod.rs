@@ -6,13 +6,21 @@
 use bevy::prelude::*;
 
 pub mod animation;
+pub mod bitplane;
 pub mod interface;
 pub mod level;
 pub mod logic;
+pub mod objective;
+pub mod pack;
+pub mod rulestring;
+pub mod simulation;
+pub mod solver;
+pub mod undo;
 
 pub(super) fn plugin(app: &mut App) {
     app.add_plugins((
         level::plugin,
+        pack::plugin,
         logic::plugin,
         interface::plugin,
         animation::plugin,
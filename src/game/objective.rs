@@ -0,0 +1,279 @@
+//! What a level asks of the player, and checking a run against it.
+//!
+//! Kept free of Bevy like [`simulation`](super::simulation), so objectives
+//! can be checked and described outside the game.
+
+use super::simulation::{ANY, Grid, Layout, Tile, is_fixed, reached};
+
+/// A level's win condition, read from its `objective` field.
+#[derive(Default, Debug, Clone, Eq, PartialEq, serde::Deserialize)]
+pub enum Objective {
+    /// The board matches the level's solution.
+    #[default]
+    Solution,
+    /// The board matches this one, laid out like the puzzle.
+    Board(Grid),
+    /// At least `count` tiles are `color`.
+    AtLeast { color: Tile, count: usize },
+    /// No tile is `color`.
+    Without(Tile),
+    /// `tiles`, `width` to a row, show up somewhere on the board. Only the rows
+    /// and columns shift, so the pattern never wraps or turns.
+    Pattern { width: usize, tiles: Grid },
+    /// Every objective is met in order, each on a later generation than the
+    /// one before it.
+    Sequence(Vec<Objective>),
+}
+
+impl Objective {
+    /// Whether the boards of a run, a generation apart and oldest first, meet
+    /// the objective. Everything but a sequence only looks at the last one.
+    pub fn achieved<'a>(
+        &self,
+        generations: impl IntoIterator<Item = &'a [u8]>,
+        solution: &[u8],
+        width: usize,
+    ) -> bool {
+        let Objective::Sequence(steps) = self else {
+            return generations
+                .into_iter()
+                .last()
+                .is_some_and(|board| self.met(board, solution, width));
+        };
+        let mut steps = steps.iter().peekable();
+        for board in generations {
+            if steps
+                .peek()
+                .is_some_and(|step| step.met(board, solution, width))
+            {
+                steps.next();
+            }
+        }
+        steps.peek().is_none()
+    }
+
+    /// Whether a single board meets the objective. A sequence nested in
+    /// another one needs all of its steps met at once.
+    fn met(&self, board: &[u8], solution: &[u8], width: usize) -> bool {
+        match self {
+            Objective::Solution => reached(board, solution),
+            Objective::Board(goal) => reached(board, goal),
+            Objective::AtLeast { color, count } => {
+                board.iter().filter(|&&value| value == *color as u8).count() >= *count
+            }
+            Objective::Without(color) => !board.contains(&(*color as u8)),
+            Objective::Pattern {
+                width: pattern_width,
+                tiles,
+            } => pattern_anywhere(board, width, tiles, *pattern_width),
+            Objective::Sequence(steps) => steps.iter().all(|step| step.met(board, solution, width)),
+        }
+    }
+
+    /// Whether tiles can be compared with the level's solution one by one.
+    pub fn is_solution(&self) -> bool {
+        *self == Objective::Solution
+    }
+
+    /// Whether the level's solution grid is part of the objective.
+    pub fn uses_solution(&self) -> bool {
+        match self {
+            Objective::Solution => true,
+            Objective::Sequence(steps) => steps.iter().any(Objective::uses_solution),
+            _ => false,
+        }
+    }
+
+    /// Values of the colors the objective asks for, so the player can pick them.
+    pub fn colors(&self) -> Vec<u8> {
+        match self {
+            Objective::Solution | Objective::Without(_) => vec![],
+            Objective::Board(tiles) | Objective::Pattern { tiles, .. } => tiles
+                .iter()
+                .copied()
+                .filter(|&value| value != ANY)
+                .collect(),
+            Objective::AtLeast { color, .. } => vec![*color as u8],
+            Objective::Sequence(steps) => steps.iter().flat_map(Objective::colors).collect(),
+        }
+    }
+
+    /// The boards the player has to see, in the order [`describe`](Self::describe)
+    /// numbers them, each with the layout to draw it in.
+    pub fn boards<'a>(&'a self, solution: &'a [u8], layout: Layout) -> Vec<(&'a [u8], Layout)> {
+        match self {
+            Objective::Solution => vec![(solution, layout)],
+            Objective::Board(tiles) => vec![(tiles, layout)],
+            Objective::Pattern { width, tiles } if *width > 0 => {
+                vec![(tiles, Layout::new(*width, tiles.len() / width))]
+            }
+            Objective::Sequence(steps) => steps
+                .iter()
+                .flat_map(|step| step.boards(solution, layout))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// A sentence telling the player what to do, referring to the boards of
+    /// [`boards`](Self::boards) by number when there are several.
+    pub fn describe(&self) -> String {
+        let numbered = self.boards(&[], Layout::default()).len() > 1;
+        let mut shown = 0;
+        let mut description = self.describe_from(numbered, &mut shown);
+        description.push('.');
+        description
+    }
+
+    fn describe_from(&self, numbered: bool, shown: &mut usize) -> String {
+        let mut board = |what: &str| {
+            *shown += 1;
+            match numbered {
+                true => format!("{what} {shown}"),
+                false => format!("the {what} shown"),
+            }
+        };
+        match self {
+            Objective::Solution | Objective::Board(_) => format!("Match {}", board("board")),
+            Objective::Pattern { .. } => format!("Make {} anywhere", board("pattern")),
+            Objective::AtLeast { color, count: 1 } => format!("Have a {color:?} tile"),
+            Objective::AtLeast { color, count } => format!("Have {count} {color:?} tiles"),
+            Objective::Without(color) => format!("Leave no {color:?} tiles"),
+            Objective::Sequence(steps) => {
+                let steps: Vec<String> = steps
+                    .iter()
+                    .enumerate()
+                    .map(|(i, step)| {
+                        let step = step.describe_from(numbered, shown);
+                        match i {
+                            0 => step,
+                            _ => format!("then {}", lowercase_first(&step)),
+                        }
+                    })
+                    .collect();
+                steps.join(", ")
+            }
+        }
+    }
+}
+
+fn lowercase_first(text: &str) -> String {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .map(|first| first.to_lowercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Whether `pattern`, `pattern_width` to a row, matches the board somewhere.
+/// Unlike a solution, the pattern has to be made of playable cells, so walls
+/// and holes match none of it.
+fn pattern_anywhere(board: &[u8], width: usize, pattern: &[u8], pattern_width: usize) -> bool {
+    if width == 0 || pattern_width == 0 || pattern_width > width {
+        return false;
+    }
+    let (height, pattern_height) = (board.len() / width, pattern.len() / pattern_width);
+    if pattern_height > height {
+        return false;
+    }
+    (0..=height - pattern_height).any(|top| {
+        (0..=width - pattern_width).any(|left| {
+            pattern.iter().enumerate().all(|(i, &goal)| {
+                let (row, column) = (i / pattern_width, i % pattern_width);
+                let value = board[(top + row) * width + left + column];
+                !is_fixed(value) && (goal == ANY || value == goal)
+            })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::simulation::{HOLE, WALL};
+
+    const R: u8 = Tile::Red as u8;
+    const G: u8 = Tile::Green as u8;
+
+    #[test]
+    fn counts_colors() {
+        let board = [R, G, G, R];
+        let at_least = |count| Objective::AtLeast {
+            color: Tile::Green,
+            count,
+        };
+        assert!(at_least(2).achieved([&board[..]], &[], 2));
+        assert!(!at_least(3).achieved([&board[..]], &[], 2));
+        assert!(Objective::Without(Tile::Blue).achieved([&board[..]], &[], 2));
+        assert!(!Objective::Without(Tile::Red).achieved([&board[..]], &[], 2));
+    }
+
+    #[test]
+    fn finds_patterns_anywhere() {
+        #[rustfmt::skip]
+        let board = [
+            R, R, R,
+            R, G, R,
+            R, G, G,
+        ];
+        let pattern = |width, tiles: &[u8]| Objective::Pattern {
+            width,
+            tiles: tiles.to_vec(),
+        };
+        assert!(pattern(2, &[G, ANY, G, G]).achieved([&board[..]], &[], 3));
+        assert!(pattern(1, &[G, G]).achieved([&board[..]], &[], 3));
+        // Rows don't carry over into the next one.
+        assert!(!pattern(3, &[R, R, G, G, R, R]).achieved([&board[..]], &[], 3));
+        assert!(!pattern(4, &[R, R, R, R]).achieved([&board[..]], &[], 3));
+    }
+
+    #[test]
+    fn patterns_skip_walls_and_holes() {
+        #[rustfmt::skip]
+        let board = [
+            WALL, WALL, R,
+            WALL, HOLE, G,
+            R,    G,    G,
+        ];
+        let pattern = |width, tiles: &[u8]| Objective::Pattern {
+            width,
+            tiles: tiles.to_vec(),
+        };
+        assert!(!pattern(2, &[G, G, G, G]).achieved([&board[..]], &[], 3));
+        assert!(!pattern(2, &[ANY, ANY, ANY, ANY]).achieved([&board[..]], &[], 3));
+        assert!(pattern(1, &[R, G, G]).achieved([&board[..]], &[], 3));
+    }
+
+    #[test]
+    fn sequences_go_in_order() {
+        let (all_red, all_green) = (vec![R; 4], vec![G; 4]);
+        let sequence = Objective::Sequence(vec![
+            Objective::Board(all_green.clone()),
+            Objective::Solution,
+        ]);
+        let run = |boards: &[&Vec<u8>]| {
+            sequence.achieved(boards.iter().map(|board| board.as_slice()), &all_red, 2)
+        };
+        assert!(run(&[&all_red, &all_green, &all_red]));
+        assert!(!run(&[&all_red, &all_green]));
+        // Red has to come after green, not before.
+        assert!(!run(&[&all_red, &all_red, &all_green]));
+    }
+
+    #[test]
+    fn descriptions_number_several_boards() {
+        assert_eq!(Objective::Solution.describe(), "Match the board shown.");
+        let sequence = Objective::Sequence(vec![
+            Objective::Solution,
+            Objective::Pattern {
+                width: 1,
+                tiles: vec![R],
+            },
+            Objective::Without(Tile::Green),
+        ]);
+        assert_eq!(
+            sequence.describe(),
+            "Match board 1, then make pattern 2 anywhere, then leave no Green tiles."
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Level packs: the files under `assets/levels`, plus any the player drops
+//! into their data directory on native builds.
+
+#[cfg(not(target_family = "wasm"))]
+use std::{env, fs, path::PathBuf};
+
+#[cfg(not(target_family = "wasm"))]
+use bevy::asset::LoadedFolder;
+use bevy::prelude::*;
+use bevy::reflect::TypePath;
+
+use super::level::{Level, LevelAssets, LevelData};
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<LevelRegistry>();
+    #[cfg(not(target_family = "wasm"))]
+    app.add_systems(Startup, load_user_packs);
+    app.add_systems(
+        Update,
+        build_level_registry.run_if(
+            resource_exists::<LevelAssets>
+                .and(resource_added::<LevelAssets>.or(on_event::<AssetEvent<LevelPack>>)),
+        ),
+    );
+}
+
+/// Browsers can't list a folder, so web builds load the shipped packs by name.
+#[cfg(target_family = "wasm")]
+pub const BUNDLED_PACKS: [&str; 1] = ["levels/shift-o.ron"];
+
+/// A pack file: who made it and its levels in play order.
+#[derive(serde::Deserialize, Asset, TypePath, Clone)]
+pub struct LevelPack {
+    pub title: String,
+    pub author: String,
+    pub levels: Vec<LevelData>,
+}
+
+/// Every pack, the shipped ones first, copied out of the pack files whenever
+/// one of them loads, then the player's own.
+#[derive(Resource, Default)]
+pub struct LevelRegistry {
+    pub packs: Vec<LevelPack>,
+}
+impl LevelRegistry {
+    pub fn get(&self, level: Level) -> Option<&LevelData> {
+        self.packs.get(level.pack)?.levels.get(level.index)
+    }
+    /// The level after `level` in its pack, starting over after the last one.
+    pub fn next(&self, level: Level) -> Level {
+        let count = self
+            .packs
+            .get(level.pack)
+            .map_or(0, |pack| pack.levels.len());
+        Level {
+            index: (level.index + 1) % count.max(1),
+            ..level
+        }
+    }
+    /// The levels of `level`'s pack up to and including it.
+    pub fn up_to(&self, level: Level) -> &[LevelData] {
+        let Some(pack) = self.packs.get(level.pack) else {
+            return &[];
+        };
+        &pack.levels[..(level.index + 1).min(pack.levels.len())]
+    }
+}
+
+fn build_level_registry(
+    level_assets: Res<LevelAssets>,
+    packs: Res<Assets<LevelPack>>,
+    #[cfg(not(target_family = "wasm"))] folders: Res<Assets<LoadedFolder>>,
+    #[cfg(not(target_family = "wasm"))] user_packs: Res<UserPacks>,
+    mut registry: ResMut<LevelRegistry>,
+) {
+    #[cfg(not(target_family = "wasm"))]
+    let mut handles: Vec<_> = folders
+        .get(&level_assets.packs)
+        .map(|folder| folder.handles.iter())
+        .into_iter()
+        .flatten()
+        .filter_map(|handle| handle.clone().try_typed::<LevelPack>().ok())
+        .collect();
+    #[cfg(target_family = "wasm")]
+    let mut handles = level_assets.packs.clone();
+    // Folders list their files in whatever order the platform does.
+    handles.sort_by_key(|handle| handle.path().map(ToString::to_string));
+
+    registry.packs = handles
+        .iter()
+        .filter_map(|handle| packs.get(handle))
+        .cloned()
+        .collect();
+    #[cfg(not(target_family = "wasm"))]
+    registry.packs.extend(user_packs.0.iter().cloned());
+}
+
+/// Where players put packs of their own, `ShiftO/levels` in the platform's
+/// data directory.
+#[cfg(not(target_family = "wasm"))]
+pub fn user_pack_dir() -> Option<PathBuf> {
+    let home = || env::var_os("HOME").map(PathBuf::from);
+    let data = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| home.join(".local/share")))
+    };
+    Some(data?.join("ShiftO").join("levels"))
+}
+
+/// The player's own packs, read from [`user_pack_dir`] once at startup.
+#[cfg(not(target_family = "wasm"))]
+#[derive(Resource)]
+struct UserPacks(Vec<LevelPack>);
+
+#[cfg(not(target_family = "wasm"))]
+fn load_user_packs(mut commands: Commands) {
+    commands.insert_resource(UserPacks(read_user_packs()));
+}
+
+/// Reads the user's packs in file name order, skipping any that don't parse.
+#[cfg(not(target_family = "wasm"))]
+fn read_user_packs() -> Vec<LevelPack> {
+    let Some(entries) = user_pack_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return vec![];
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let pack = fs::read_to_string(&path)
+                .map_err(|error| error.to_string())
+                .and_then(|file| ron::from_str(&file).map_err(|error| error.to_string()));
+            pack.inspect_err(|error| warn!("Skipping level pack {}: {error}", path.display()))
+                .ok()
+        })
+        .collect()
+}
@@ -0,0 +1,193 @@
+//! Golly-style rulestrings for Life-like automata, such as `B3/S23`.
+//!
+//! A rulestring lists the live neighbour counts at which a dead cell is born
+//! and a live cell survives. [`Rulestring::rules`] spells it out as ordinary
+//! rules for two colors, so it runs through the same [`simulation`] as any level.
+//!
+//! [`simulation`]: super::simulation
+
+use std::{fmt, str::FromStr};
+
+use super::simulation::{Count, Rule, RuleSet, Tile};
+
+/// Neighbour counts from 0 to 8 at which cells are born or survive.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Rulestring {
+    pub birth: [bool; 9],
+    pub survival: [bool; 9],
+}
+
+impl Rulestring {
+    /// Dead cells get one rule per run of consecutive birth counts, live cells
+    /// one per run of counts they don't survive.
+    pub fn rules(&self, dead: Tile, alive: Tile) -> RuleSet {
+        let rules = |counts: [bool; 9], result| {
+            runs(counts)
+                .into_iter()
+                .map(|count| Rule {
+                    tiles: [Some(alive), None],
+                    count,
+                    result: Some(result),
+                    ..Rule::default()
+                })
+                .collect()
+        };
+        RuleSet::from([
+            (dead, rules(self.birth, alive)),
+            (alive, rules(self.survival.map(|survives| !survives), dead)),
+        ])
+    }
+}
+
+/// Splits the set counts into the fewest `Count` ranges covering exactly them.
+fn runs(counts: [bool; 9]) -> Vec<Count> {
+    let mut runs: Vec<Count> = vec![];
+    for (count, _) in counts.iter().enumerate().filter(|&(_, &set)| set) {
+        let count = count as u8;
+        match runs.last_mut() {
+            Some(run) if run.max + 1 == count => run.max = count,
+            _ => runs.push(Count::exactly(count)),
+        }
+    }
+    runs
+}
+
+/// Reads `B3/S23` style strings in either order and any case, as well as the
+/// older `23/3` form that lists survivals first.
+impl FromStr for Rulestring {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let Some((first, second)) = text.split_once('/') else {
+            return Err(format!("{text:?} needs a '/' between births and survivals"));
+        };
+        let prefix = |part: &str| part.chars().next().filter(char::is_ascii_alphabetic);
+        let (birth, survival) = match (prefix(first), prefix(second)) {
+            (Some('B' | 'b'), Some('S' | 's')) => (&first[1..], &second[1..]),
+            (Some('S' | 's'), Some('B' | 'b')) => (&second[1..], &first[1..]),
+            (None, None) => (second, first),
+            _ => return Err(format!("{text:?} should look like B3/S23")),
+        };
+        Ok(Rulestring {
+            birth: counts(birth)?,
+            survival: counts(survival)?,
+        })
+    }
+}
+
+fn counts(digits: &str) -> Result<[bool; 9], String> {
+    let mut counts = [false; 9];
+    for digit in digits.chars() {
+        match digit.to_digit(10) {
+            Some(count) if (count as usize) < counts.len() => counts[count as usize] = true,
+            _ => return Err(format!("{digit:?} is not a neighbour count from 0 to 8")),
+        }
+    }
+    Ok(counts)
+}
+
+impl fmt::Display for Rulestring {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = |counts: [bool; 9]| -> String {
+            (0..counts.len())
+                .filter(|&count| counts[count])
+                .map(|count| count.to_string())
+                .collect()
+        };
+        write!(f, "B{}/S{}", digits(self.birth), digits(self.survival))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::simulation::{Boundary, Layout, step};
+
+    const D: u8 = Tile::Blue as u8;
+    const A: u8 = Tile::Yellow as u8;
+
+    fn life() -> RuleSet {
+        "B3/S23"
+            .parse::<Rulestring>()
+            .unwrap()
+            .rules(Tile::Blue, Tile::Yellow)
+    }
+
+    #[test]
+    fn parses_golly_notation() {
+        let life: Rulestring = "B3/S23".parse().unwrap();
+        assert_eq!("s23/b3".parse(), Ok(life));
+        assert_eq!("23/3".parse(), Ok(life));
+        assert_eq!(life.to_string(), "B3/S23");
+        assert_eq!("B2/S".parse::<Rulestring>().unwrap().to_string(), "B2/S");
+    }
+
+    #[test]
+    fn rejects_malformed_rulestrings() {
+        assert!("B3S23".parse::<Rulestring>().is_err());
+        assert!("B39/S23".parse::<Rulestring>().is_err());
+        assert!("B3/X23".parse::<Rulestring>().is_err());
+    }
+
+    #[test]
+    fn counts_become_ranges() {
+        let rules = "B3678/S34678"
+            .parse::<Rulestring>()
+            .unwrap()
+            .rules(Tile::Blue, Tile::Yellow);
+        let counts = |tile| -> Vec<Count> { rules[&tile].iter().map(|rule| rule.count).collect() };
+        assert_eq!(
+            counts(Tile::Blue),
+            vec![Count::exactly(3), Count::between(6, 8)]
+        );
+        assert_eq!(
+            counts(Tile::Yellow),
+            vec![Count::between(0, 2), Count::exactly(5)]
+        );
+    }
+
+    #[test]
+    fn blinker_oscillates() {
+        let layout = Layout::new(5, 5);
+        #[rustfmt::skip]
+        let across = vec![
+            D, D, D, D, D,
+            D, D, D, D, D,
+            D, A, A, A, D,
+            D, D, D, D, D,
+            D, D, D, D, D,
+        ];
+        #[rustfmt::skip]
+        let down = vec![
+            D, D, D, D, D,
+            D, D, A, D, D,
+            D, D, A, D, D,
+            D, D, A, D, D,
+            D, D, D, D, D,
+        ];
+        assert_eq!(step(&across, layout, &life()), down);
+        assert_eq!(step(&down, layout, &life()), across);
+    }
+
+    #[test]
+    fn glider_crosses_a_wrapped_board() {
+        let layout = Layout::new(6, 6).with_boundary(Boundary::Wrap);
+        #[rustfmt::skip]
+        let glider = vec![
+            A, A, A, D, D, D,
+            A, D, D, D, D, D,
+            D, A, D, D, D, D,
+            D, D, D, D, D, D,
+            D, D, D, D, D, D,
+            D, D, D, D, D, D,
+        ];
+        // A glider moves one cell diagonally every four generations, so after
+        // 24 it is back where it started on a 6x6 torus.
+        let mut grid = glider.clone();
+        for _ in 0..24 {
+            grid = step(&grid, layout, &life());
+        }
+        assert_eq!(grid, glider);
+    }
+}
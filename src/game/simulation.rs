@@ -0,0 +1,1103 @@
+//! The cellular automaton at the heart of the game, free of any Bevy types.
+//!
+//! The gameplay systems, the solver and the level tooling all step grids
+//! through this module, so they can never disagree about what a rule does.
+
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use super::bitplane::{self, Planes};
+
+/// A board stored row by row, one [`Tile`] index per cell.
+pub type Grid = Vec<u8>;
+/// The rules each color follows during a step, in the order they are tried.
+pub type RuleSet = HashMap<Tile, Vec<Rule>>;
+
+#[derive(Default, Debug, Copy, Clone, Eq, Hash, PartialEq, PartialOrd, Ord, serde::Deserialize)]
+pub enum Tile {
+    Red,
+    Green,
+    Blue,
+    Yellow,
+    Orange,
+    Purple,
+    Brown,
+    Pink,
+    #[default]
+    Empty,
+}
+
+/// A goal cell that any color satisfies. Reads as [`Tile::Empty`] like any
+/// other value past the colors.
+pub const ANY: u8 = u8::MAX;
+
+/// A cell that never changes, drawn as a block and seen by its neighbours as
+/// [`Layout::wall`].
+pub const WALL: u8 = 254;
+/// A gap in the board: it never changes, isn't drawn and is seen by its
+/// neighbours as [`Layout::hole`].
+pub const HOLE: u8 = 253;
+
+/// Whether `value` is a [`WALL`] or a [`HOLE`], which no rule ever changes.
+pub fn is_fixed(value: u8) -> bool {
+    value == WALL || value == HOLE
+}
+
+/// Whether a cell holding `value` satisfies the goal cell `goal`. Walls and
+/// holes satisfy anything, they can't be made to match.
+pub fn matches(value: u8, goal: u8) -> bool {
+    goal == ANY || is_fixed(value) || value == goal
+}
+
+/// Whether every cell of `grid` satisfies its cell in `goal`.
+pub fn reached(grid: &[u8], goal: &[u8]) -> bool {
+    grid.len() == goal.len()
+        && grid
+            .iter()
+            .zip(goal)
+            .all(|(&value, &goal)| matches(value, goal))
+}
+
+impl Tile {
+    pub fn from_u8(value: u8) -> Tile {
+        match value {
+            0 => Tile::Red,
+            1 => Tile::Green,
+            2 => Tile::Blue,
+            3 => Tile::Yellow,
+            4 => Tile::Orange,
+            5 => Tile::Purple,
+            6 => Tile::Brown,
+            7 => Tile::Pink,
+            _ => Tile::Empty,
+        }
+    }
+    pub fn all() -> [Tile; 9] {
+        [
+            Tile::Red,
+            Tile::Green,
+            Tile::Blue,
+            Tile::Yellow,
+            Tile::Orange,
+            Tile::Purple,
+            Tile::Brown,
+            Tile::Pink,
+            Tile::Empty,
+        ]
+    }
+}
+
+/// How many of the masked neighbours have to be a rule's tile, from `min` to
+/// `max` inclusive. The default of one or more reads as "any".
+#[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Deserialize)]
+pub struct Count {
+    pub min: u8,
+    pub max: u8,
+}
+impl Count {
+    /// The largest neighbourhood any layout has.
+    pub const MAX: u8 = MAX_NEIGHBOURS as u8;
+
+    pub fn at_least(min: u8) -> Count {
+        Count {
+            min,
+            max: Count::MAX,
+        }
+    }
+    pub fn exactly(count: u8) -> Count {
+        Count {
+            min: count,
+            max: count,
+        }
+    }
+    pub fn between(min: u8, max: u8) -> Count {
+        Count { min, max }
+    }
+    pub fn contains(&self, count: usize) -> bool {
+        (self.min as usize..=self.max as usize).contains(&count)
+    }
+}
+impl Default for Count {
+    fn default() -> Self {
+        Count::at_least(1)
+    }
+}
+
+/// How the checks on a rule's tiles add up to the rule firing.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, serde::Deserialize)]
+pub enum Combinator {
+    #[default]
+    AnyOf,
+    AllOf,
+    NoneOf,
+}
+impl Combinator {
+    pub fn next(self) -> Combinator {
+        match self {
+            Combinator::AnyOf => Combinator::AllOf,
+            Combinator::AllOf => Combinator::NoneOf,
+            Combinator::NoneOf => Combinator::AnyOf,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub tiles: [Option<Tile>; 2],
+    pub combinator: Combinator,
+    pub count: Count,
+    pub invert: bool,
+    pub mask: [bool; MAX_NEIGHBOURS],
+    /// Mask cells already toggled by the current drag, only used by the rule editor.
+    pub changed: [bool; MAX_NEIGHBOURS],
+    pub result: Option<Tile>,
+    /// How often, in percent, the rule goes through when it fires.
+    pub chance: u8,
+    /// Parts the level doesn't let the player change, only used by the rule editor.
+    pub locked: Locks,
+    /// Which of its color's presets the rule started as, wherever it has moved
+    /// since. Only used by the rule editor, to reset it.
+    pub preset: Option<usize>,
+}
+impl Rule {
+    /// The chance of a rule that always goes through.
+    pub const ALWAYS: u8 = 100;
+
+    /// Whether the rule applies to a cell with these `neighbours`, ignoring
+    /// directions the board doesn't have.
+    pub fn fires(
+        &self,
+        neighbours: &[Tile; MAX_NEIGHBOURS],
+        directions: [bool; MAX_NEIGHBOURS],
+    ) -> bool {
+        // A rule without tiles never fires, whatever the combinator.
+        if self.tiles.iter().all(Option::is_none) {
+            return false;
+        }
+        let masked: Vec<Tile> = neighbours
+            .iter()
+            .zip(self.mask)
+            .zip(directions)
+            .filter(|&((_, enabled), exists)| enabled && exists)
+            .map(|((&tile, _), _)| tile)
+            .collect();
+        let mut checks = self.tiles.iter().flatten().map(|tile| {
+            let found = masked.iter().filter(|&neighbour| neighbour == tile).count();
+            self.count.contains(found) ^ self.invert
+        });
+        match self.combinator {
+            Combinator::AnyOf => checks.any(|check| check),
+            Combinator::AllOf => checks.all(|check| check),
+            Combinator::NoneOf => !checks.any(|check| check),
+        }
+    }
+}
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            tiles: [None, None],
+            combinator: Combinator::default(),
+            count: Count::default(),
+            invert: false,
+            mask: [true; MAX_NEIGHBOURS],
+            changed: [false; MAX_NEIGHBOURS],
+            result: None,
+            chance: Rule::ALWAYS,
+            locked: Locks::default(),
+            preset: None,
+        }
+    }
+}
+
+/// Parts of a [`Rule`] a level keeps the player from changing. Locking the
+/// whole `rule` locks every part, its count, combinator and chance included.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct Locks {
+    pub rule: bool,
+    pub tiles: bool,
+    pub mask: bool,
+    pub invert: bool,
+    pub result: bool,
+}
+impl Locks {
+    pub fn tiles(self) -> bool {
+        self.rule || self.tiles
+    }
+    pub fn mask(self) -> bool {
+        self.rule || self.mask
+    }
+    pub fn invert(self) -> bool {
+        self.rule || self.invert
+    }
+    pub fn result(self) -> bool {
+        self.rule || self.result
+    }
+    /// Whether anything is locked, which keeps the rule in its place in the list.
+    pub fn any(self) -> bool {
+        self != Locks::default()
+    }
+}
+
+/// How the cells of a [`Grid`] are arranged: `width` cells per row, `height`
+/// rows, with row 0 at the bottom of the board. Also carries the [`Schedule`]
+/// they update in, so every caller of [`step`] plays a board the same way.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Layout {
+    pub width: usize,
+    pub height: usize,
+    pub boundary: Boundary,
+    pub topology: Topology,
+    pub radius: Radius,
+    pub schedule: Schedule,
+    /// What a [`WALL`] cell looks like to its neighbours.
+    pub wall: Tile,
+    /// What a [`HOLE`] cell looks like to its neighbours.
+    pub hole: Tile,
+}
+
+/// How far from a cell its neighbours reach.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, serde::Deserialize)]
+pub enum Radius {
+    /// The cells touching it.
+    #[default]
+    One,
+    /// The 5x5 block around it, adding the ring of sixteen cells past the
+    /// touching ones. Only square boards have it.
+    Two,
+}
+
+/// The order the cells of a board update in during a generation.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, serde::Deserialize)]
+pub enum Schedule {
+    /// Every cell at once, reading only the previous generation.
+    #[default]
+    Sync,
+    /// One cell at a time in grid order, each seeing the cells updated before
+    /// it. Every row is a phase of its own.
+    Sequential,
+    /// First every cell whose column and row add up to an even number, then
+    /// the odd ones, each half at once.
+    Checkerboard,
+}
+
+impl Schedule {
+    /// How many phases a generation on `layout` takes, each shown as a step of its own.
+    pub fn phases(self, layout: Layout) -> usize {
+        match self {
+            Schedule::Sync => 1,
+            Schedule::Sequential => layout.height.max(1),
+            Schedule::Checkerboard => 2,
+        }
+    }
+}
+
+/// The shape of the cells, which decides who neighbours whom.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, serde::Deserialize)]
+pub enum Topology {
+    /// Square cells with eight neighbours each.
+    #[default]
+    Square,
+    /// Hexagonal cells with six neighbours each, odd rows shifted half a cell to the right.
+    /// Wrapping boards need an even height for the shifted rows to line up.
+    Hex,
+}
+
+impl Topology {
+    /// Which [`Rule::mask`] entries are neighbours. Hex cells have no neighbour
+    /// straight above or below, so entries 1 and 6 go unused.
+    pub fn directions(self) -> [bool; 8] {
+        match self {
+            Topology::Square => [true; 8],
+            Topology::Hex => [true, false, true, true, true, true, false, true],
+        }
+    }
+
+    /// Column and row offsets of the neighbours of a cell in `row`, in [`Rule::mask`] order.
+    /// Hex cells have no outer ring, so its entries stay at the cell itself.
+    pub(crate) fn offsets(self, row: usize) -> [(isize, isize); MAX_NEIGHBOURS] {
+        match self {
+            Topology::Square => NEIGHBOUR_OFFSETS,
+            Topology::Hex => {
+                let shift = (row % 2) as isize;
+                let near = [
+                    (shift - 1, -1),
+                    (0, 0),
+                    (shift, -1),
+                    (-1, 0),
+                    (1, 0),
+                    (shift - 1, 1),
+                    (0, 0),
+                    (shift, 1),
+                ];
+                std::array::from_fn(|i| near.get(i).copied().unwrap_or((0, 0)))
+            }
+        }
+    }
+}
+
+/// What a cell on the edge of the board sees past it.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, serde::Deserialize)]
+pub enum Boundary {
+    /// Everything outside the board is [`Tile::Empty`].
+    #[default]
+    Empty,
+    /// The board is a torus: the left edge sees the right column, the bottom sees the top row.
+    Wrap,
+    /// The edge reflects, so a cell sees the board mirrored past it: one cell
+    /// out is the edge row or column itself, two cells out the one before it.
+    Mirror,
+}
+
+impl Boundary {
+    /// Maps a coordinate any distance past either end of a side `size` cells
+    /// long back onto the board.
+    pub(crate) fn resolve(self, coordinate: isize, size: usize) -> Option<usize> {
+        let size = size as isize;
+        if (0..size).contains(&coordinate) {
+            return Some(coordinate as usize);
+        }
+        match self {
+            Boundary::Empty => None,
+            Boundary::Wrap => Some(coordinate.rem_euclid(size) as usize),
+            // The board and its reflection repeat every two sides.
+            Boundary::Mirror => {
+                let folded = coordinate.rem_euclid(2 * size);
+                Some(folded.min(2 * size - 1 - folded) as usize)
+            }
+        }
+    }
+}
+
+impl Layout {
+    pub fn new(width: usize, height: usize) -> Layout {
+        Layout {
+            width,
+            height,
+            boundary: Boundary::Empty,
+            topology: Topology::Square,
+            radius: Radius::One,
+            schedule: Schedule::Sync,
+            wall: Tile::Empty,
+            hole: Tile::Empty,
+        }
+    }
+    pub fn with_topology(self, topology: Topology) -> Layout {
+        Layout { topology, ..self }
+    }
+    pub fn with_boundary(self, boundary: Boundary) -> Layout {
+        Layout { boundary, ..self }
+    }
+    pub fn with_radius(self, radius: Radius) -> Layout {
+        Layout { radius, ..self }
+    }
+    pub fn with_schedule(self, schedule: Schedule) -> Layout {
+        Layout { schedule, ..self }
+    }
+    pub fn with_walls(self, wall: Tile, hole: Tile) -> Layout {
+        Layout { wall, hole, ..self }
+    }
+    /// The tile a cell holding `value` looks like to its neighbours.
+    pub fn seen_as(self, value: u8) -> Tile {
+        match value {
+            WALL => self.wall,
+            HOLE => self.hole,
+            value => Tile::from_u8(value),
+        }
+    }
+    /// Which [`Rule::mask`] entries are neighbours: those the topology has,
+    /// plus the outer ring when a square board has a radius of two.
+    pub fn directions(self) -> [bool; MAX_NEIGHBOURS] {
+        let near = self.topology.directions();
+        let far = self.radius == Radius::Two && self.topology == Topology::Square;
+        std::array::from_fn(|i| near.get(i).copied().unwrap_or(far))
+    }
+    /// How many neighbours each cell has.
+    pub fn neighbour_count(self) -> u8 {
+        self.directions().iter().filter(|&&exists| exists).count() as u8
+    }
+    pub fn len(&self) -> usize {
+        self.width * self.height
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The number of [`Rule::mask`] entries, enough for a radius of two.
+pub const MAX_NEIGHBOURS: usize = 24;
+
+/// Column and row offsets of the square neighbours, in [`Rule::mask`] order:
+/// the eight touching cells first, then the outer ring of the 5x5 block, each
+/// from the bottom row up and left to right.
+pub const NEIGHBOUR_OFFSETS: [(isize, isize); MAX_NEIGHBOURS] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (-2, -2),
+    (-1, -2),
+    (0, -2),
+    (1, -2),
+    (2, -2),
+    (-2, -1),
+    (2, -1),
+    (-2, 0),
+    (2, 0),
+    (-2, 1),
+    (2, 1),
+    (-2, 2),
+    (-1, 2),
+    (0, 2),
+    (1, 2),
+    (2, 2),
+];
+
+/// Returns the positions of the neighbours of the cell at `index` in
+/// [`Rule::mask`] order, or `None` for those outside the board and for
+/// directions the [`Layout`] doesn't have.
+pub fn neighbour_indices(layout: Layout, index: usize) -> [Option<usize>; MAX_NEIGHBOURS] {
+    let (column, row) = (index % layout.width, index / layout.width);
+    let directions = layout.directions();
+    let offsets = layout.topology.offsets(row);
+    std::array::from_fn(|i| {
+        if !directions[i] {
+            return None;
+        }
+        let (dx, dy) = offsets[i];
+        let column = layout
+            .boundary
+            .resolve(column as isize + dx, layout.width)?;
+        let row = layout.boundary.resolve(row as isize + dy, layout.height)?;
+        Some(row * layout.width + column)
+    })
+}
+
+/// Returns the neighbours of the cell at `index` in [`Rule::mask`] order, with
+/// cells past a [`Boundary::Empty`] edge reading as [`Tile::Empty`] and walls
+/// and holes as the layout [`sees them`](Layout::seen_as). Directions the
+/// [`Layout`] doesn't have read as [`Tile::Empty`] too, but are never masked in.
+pub fn neighbours(grid: &[u8], layout: Layout, index: usize) -> [Tile; MAX_NEIGHBOURS] {
+    neighbour_indices(layout, index).map(|neighbor| match neighbor {
+        Some(neighbor) => layout.seen_as(grid[neighbor]),
+        None => Tile::Empty,
+    })
+}
+
+/// Returns the tile the cell at `index` turns into, or `None` if it stays unchanged.
+/// The first of the cell's rules that fires, has a result and makes its chance wins.
+/// Only rules with a [`Rule::chance`] below [`Rule::ALWAYS`] draw from `rng`.
+/// Walls and holes never change.
+pub fn check_neighbours(
+    grid: &[u8],
+    layout: Layout,
+    index: usize,
+    rules: &RuleSet,
+    rng: &mut impl Rng,
+) -> Option<Tile> {
+    if is_fixed(grid[index]) {
+        return None;
+    }
+    let neighbours = neighbours(grid, layout, index);
+    let directions = layout.directions();
+    rules
+        .get(&Tile::from_u8(grid[index]))?
+        .iter()
+        .filter(|rule| rule.fires(&neighbours, directions) && rule.result.is_some())
+        .find(|rule| rule.chance >= Rule::ALWAYS || rng.gen_range(0..Rule::ALWAYS) < rule.chance)
+        .and_then(|rule| rule.result)
+}
+
+/// Returns the colors a player can pick from: every color on either board, sorted.
+pub fn color_pool(grid: &[u8], goal: &[u8]) -> Vec<Tile> {
+    let mut pool: Vec<Tile> = grid
+        .iter()
+        .chain(goal)
+        .filter(|&&value| value != ANY && !is_fixed(value))
+        .map(|&value| Tile::from_u8(value))
+        .collect();
+    pool.sort();
+    pool.dedup();
+    pool
+}
+
+/// Applies the rules to the cells `phase` of the layout's [`Schedule`] covers.
+/// Chances are rolled cell by cell in grid order, so the same `rng` state
+/// always gives the same board.
+///
+/// Boards the packed [`bitplane`] backend [`supports`](bitplane::supports) are
+/// stepped through it, the rest one cell at a time with [`step_cells`].
+pub fn step_phase(
+    grid: &[u8],
+    layout: Layout,
+    rules: &RuleSet,
+    phase: usize,
+    rng: &mut impl Rng,
+) -> Grid {
+    if bitplane::supports(layout, rules) {
+        return Planes::from_grid(grid, layout)
+            .step_phase(rules, phase)
+            .to_grid();
+    }
+    step_cells(grid, layout, rules, phase, rng)
+}
+
+/// [`step_phase`] one cell at a time through [`check_neighbours`], whatever the rules.
+pub fn step_cells(
+    grid: &[u8],
+    layout: Layout,
+    rules: &RuleSet,
+    phase: usize,
+    rng: &mut impl Rng,
+) -> Grid {
+    let mut new_grid = grid.to_vec();
+    match layout.schedule {
+        Schedule::Sync | Schedule::Checkerboard => {
+            let parity = |i: usize| (i % layout.width + i / layout.width) % 2;
+            for (i, new_tile) in new_grid.iter_mut().enumerate() {
+                if layout.schedule == Schedule::Checkerboard && parity(i) != phase {
+                    continue;
+                }
+                if let Some(tile) = check_neighbours(grid, layout, i, rules, rng) {
+                    *new_tile = tile as u8;
+                }
+            }
+        }
+        Schedule::Sequential => {
+            let row = phase * layout.width..(phase + 1) * layout.width;
+            for i in row {
+                if let Some(tile) = check_neighbours(&new_grid, layout, i, rules, rng) {
+                    new_grid[i] = tile as u8;
+                }
+            }
+        }
+    }
+    new_grid
+}
+
+/// Runs a whole generation, every phase of the layout's [`Schedule`] in turn.
+pub fn step_seeded(grid: &[u8], layout: Layout, rules: &RuleSet, rng: &mut impl Rng) -> Grid {
+    let mut grid = grid.to_vec();
+    for phase in 0..layout.schedule.phases(layout) {
+        grid = step_phase(&grid, layout, rules, phase, rng);
+    }
+    grid
+}
+
+/// [`step_seeded`] with a fixed seed, for callers whose rules don't roll or
+/// that don't care which way the rolls go.
+pub fn step(grid: &[u8], layout: Layout, rules: &RuleSet) -> Grid {
+    step_seeded(grid, layout, rules, &mut StdRng::seed_from_u64(0))
+}
+
+/// Whether any rule rolls for its chance. A board coming back then doesn't
+/// mean the run repeats, the rolls can go differently the second time.
+pub fn rolls(rules: &RuleSet) -> bool {
+    rules
+        .values()
+        .flatten()
+        .any(|rule| rule.chance < Rule::ALWAYS)
+}
+
+/// How a run settles once a board comes back.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Settled {
+    /// Nothing changes any more from generation `after` on.
+    Stable { after: usize },
+    /// The boards repeat every `period` generations.
+    Loop { period: usize },
+}
+
+/// Remembers the boards of a run by their hash and notices the first one that
+/// comes back. The boards themselves stay in the run's history, which a
+/// matching hash is checked against in case two boards share one.
+#[derive(Default, Debug, Clone)]
+pub struct Recurrence {
+    seen: HashMap<u64, Vec<usize>>,
+}
+
+impl Recurrence {
+    pub fn clear(&mut self) {
+        self.seen.clear();
+    }
+
+    /// Records `grid` as generation `generation`, returning how the run settles
+    /// if an earlier generation had the same board. `board_at` gives the board
+    /// of an earlier generation from the history.
+    pub fn record<'a>(
+        &mut self,
+        generation: usize,
+        grid: &[u8],
+        board_at: impl Fn(usize) -> &'a [u8],
+    ) -> Option<Settled> {
+        let mut hasher = DefaultHasher::new();
+        grid.hash(&mut hasher);
+        self.record_hashed(hasher.finish(), generation, grid, board_at)
+    }
+
+    fn record_hashed<'a>(
+        &mut self,
+        hash: u64,
+        generation: usize,
+        grid: &[u8],
+        board_at: impl Fn(usize) -> &'a [u8],
+    ) -> Option<Settled> {
+        let generations = self.seen.entry(hash).or_default();
+        if let Some(&earlier) = generations
+            .iter()
+            .find(|&&earlier| board_at(earlier) == grid)
+        {
+            return Some(match generation - earlier {
+                1 => Settled::Stable { after: earlier },
+                period => Settled::Loop { period },
+            });
+        }
+        generations.push(generation);
+        None
+    }
+}
+
+/// Returns the history of `grid` under `rules`, starting with `grid` itself
+/// and stepping until it holds `max` generations, the same cap as the gameplay loop.
+pub fn run(grid: &[u8], layout: Layout, rules: &RuleSet, max: usize) -> Vec<Grid> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut history = vec![grid.to_vec()];
+    while history.len() < max {
+        let next = step_seeded(history.last().unwrap(), layout, rules, &mut rng);
+        history.push(next);
+    }
+    history
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const R: u8 = Tile::Red as u8;
+    const G: u8 = Tile::Green as u8;
+    const SMALL: Layout = Layout {
+        width: 2,
+        height: 2,
+        boundary: Boundary::Empty,
+        topology: Topology::Square,
+        radius: Radius::One,
+        schedule: Schedule::Sync,
+        wall: Tile::Empty,
+        hole: Tile::Empty,
+    };
+    const SQUARE: Layout = Layout {
+        width: 3,
+        height: 3,
+        boundary: Boundary::Empty,
+        topology: Topology::Square,
+        radius: Radius::One,
+        schedule: Schedule::Sync,
+        wall: Tile::Empty,
+        hole: Tile::Empty,
+    };
+
+    /// A mask over the touching cells only.
+    fn near(mask: [bool; 8]) -> [bool; MAX_NEIGHBOURS] {
+        std::array::from_fn(|i| i < 8 && mask[i])
+    }
+
+    fn rule(tiles: [Option<Tile>; 2], result: Tile) -> Rule {
+        Rule {
+            tiles,
+            result: Some(result),
+            ..Rule::default()
+        }
+    }
+
+    #[test]
+    fn no_rules_leave_grid_unchanged() {
+        let grid = vec![R, G, G, R];
+        assert_eq!(step(&grid, SMALL, &RuleSet::new()), grid);
+    }
+
+    #[test]
+    fn rule_without_result_does_nothing() {
+        let grid = vec![R, G, G, R];
+        let rules = RuleSet::from([(
+            Tile::Red,
+            vec![Rule {
+                tiles: [Some(Tile::Green), None],
+                ..Rule::default()
+            }],
+        )]);
+        assert_eq!(step(&grid, SMALL, &rules), grid);
+    }
+
+    #[test]
+    fn cells_update_from_previous_generation() {
+        // Green spreads by one cell per step, not across the whole row at once.
+        let grid = vec![G, R, R, R, R, R, R, R, R];
+        let rules = RuleSet::from([(
+            Tile::Red,
+            vec![rule([Some(Tile::Green), None], Tile::Green)],
+        )]);
+        assert_eq!(step(&grid, SQUARE, &rules), vec![G, G, R, G, G, R, R, R, R]);
+    }
+
+    #[test]
+    fn edges_do_not_wrap() {
+        // The last cell of the first row must not see the first cell of the second row.
+        let grid = vec![R, R, R, G, R, R, R, R, R];
+        let mut only_right = rule([Some(Tile::Green), None], Tile::Green);
+        only_right.mask = near([false, false, false, false, true, false, false, false]);
+        let rules = RuleSet::from([(Tile::Red, vec![only_right])]);
+        assert_eq!(step(&grid, SQUARE, &rules), grid);
+    }
+
+    #[test]
+    fn outside_the_board_is_empty() {
+        let grid = vec![R, R, R, R];
+        let rules = RuleSet::from([(
+            Tile::Red,
+            vec![rule([Some(Tile::Empty), None], Tile::Green)],
+        )]);
+        assert_eq!(step(&grid, SMALL, &rules), vec![G, G, G, G]);
+    }
+
+    #[test]
+    fn walls_and_holes_never_change() {
+        #[rustfmt::skip]
+        let grid = vec![
+            R, WALL, R,
+            R, HOLE, R,
+            R, R, R,
+        ];
+        let rules = RuleSet::from([
+            (
+                Tile::Red,
+                vec![rule([Some(Tile::Green), None], Tile::Green)],
+            ),
+            // Fixed cells read as Empty, but its rules leave them alone.
+            (Tile::Empty, vec![rule([Some(Tile::Red), None], Tile::Red)]),
+        ]);
+        let layout = SQUARE.with_walls(Tile::Green, Tile::Empty);
+        #[rustfmt::skip]
+        let expected = vec![
+            G, WALL, G,
+            G, HOLE, G,
+            R, R, R,
+        ];
+        assert_eq!(step(&grid, layout, &rules), expected);
+        // Nothing has to match them either.
+        assert!(reached(&expected, &[G, R, G, G, G, G, R, R, R]));
+    }
+
+    #[test]
+    fn invert_negates_each_tile() {
+        let grid = vec![G, R, R, R, R, R, R, R, R];
+        let mut lonely = rule([Some(Tile::Green), None], Tile::Green);
+        lonely.invert = true;
+        let rules = RuleSet::from([(Tile::Red, vec![lonely])]);
+        assert_eq!(step(&grid, SQUARE, &rules), vec![G, R, G, R, R, G, G, G, G]);
+    }
+
+    #[test]
+    fn run_is_capped_at_max_generations() {
+        let grid = vec![R, R, R, R, G, R, R, R, R];
+        let rules = RuleSet::from([(
+            Tile::Red,
+            vec![rule([Some(Tile::Green), None], Tile::Green)],
+        )]);
+        let history = run(&grid, SQUARE, &rules, 5);
+        assert_eq!(history.len(), 5);
+        assert_eq!(history[0], grid);
+        assert_eq!(history[1], vec![G; 9]);
+        assert_eq!(run(&grid, SQUARE, &rules, 0), vec![grid]);
+    }
+
+    #[test]
+    fn rows_end_at_the_layout_width() {
+        // Only the cell below the green one may see it, through its upper neighbour.
+        let layout = Layout::new(4, 2);
+        let grid = vec![R, R, R, R, G, R, R, R];
+        let mut up_or_right = rule([Some(Tile::Green), None], Tile::Green);
+        up_or_right.mask = near([false, false, false, false, true, false, true, false]);
+        let rules = RuleSet::from([(Tile::Red, vec![up_or_right])]);
+        assert_eq!(step(&grid, layout, &rules), vec![G, R, R, R, G, R, R, R]);
+    }
+
+    #[test]
+    fn wrapped_edges_see_the_opposite_side() {
+        let layout = Layout::new(4, 2).with_boundary(Boundary::Wrap);
+        let grid = vec![R, R, R, G, R, R, R, R];
+        let mut only_left = rule([Some(Tile::Green), None], Tile::Green);
+        only_left.mask = near([false, false, false, true, false, false, false, false]);
+        let rules = RuleSet::from([(Tile::Red, vec![only_left])]);
+        assert_eq!(step(&grid, layout, &rules), vec![G, R, R, G, R, R, R, R]);
+        // Below the bottom row is the top row.
+        assert_eq!(neighbour_indices(layout, 0)[1], Some(4));
+    }
+
+    #[test]
+    fn mirrored_edges_reflect_the_board() {
+        let layout = Layout::new(3, 2).with_boundary(Boundary::Mirror);
+        assert_eq!(
+            neighbour_indices(layout, 0)[..8],
+            [
+                Some(0),
+                Some(0),
+                Some(1),
+                Some(0),
+                Some(1),
+                Some(3),
+                Some(3),
+                Some(4)
+            ]
+        );
+        assert_eq!(
+            neighbour_indices(layout, 5)[4..8],
+            [Some(5), Some(4), Some(5), Some(5)]
+        );
+    }
+
+    #[test]
+    fn hex_rows_are_offset() {
+        let layout = Layout::new(3, 3).with_topology(Topology::Hex);
+        // The middle row is shifted right, so it touches the row below on its right side.
+        assert_eq!(
+            neighbour_indices(layout, 4)[..8],
+            [
+                Some(1),
+                None,
+                Some(2),
+                Some(3),
+                Some(5),
+                Some(7),
+                None,
+                Some(8)
+            ]
+        );
+        assert_eq!(
+            neighbour_indices(layout, 1)[..8],
+            [None, None, None, Some(0), Some(2), Some(3), None, Some(4)]
+        );
+    }
+
+    #[test]
+    fn hex_cells_ignore_missing_directions() {
+        // A full mask must not see the empty slots straight above and below.
+        let layout = Layout::new(3, 3).with_topology(Topology::Hex);
+        let grid = vec![R; 9];
+        let rules = RuleSet::from([(
+            Tile::Red,
+            vec![rule([Some(Tile::Empty), None], Tile::Green)],
+        )]);
+        assert_eq!(step(&grid, layout, &rules), vec![G, G, G, G, R, G, G, G, G]);
+    }
+
+    #[test]
+    fn first_firing_rule_wins() {
+        let grid = vec![G, R, R, R, R, R, R, R, R];
+        // A rule without a result never decides, and green neighbours beat everything below.
+        let rules = RuleSet::from([(
+            Tile::Red,
+            vec![
+                Rule {
+                    tiles: [Some(Tile::Green), None],
+                    ..Rule::default()
+                },
+                rule([Some(Tile::Green), None], Tile::Green),
+                rule([Some(Tile::Red), None], Tile::Blue),
+            ],
+        )]);
+        let b = Tile::Blue as u8;
+        assert_eq!(step(&grid, SQUARE, &rules), vec![G, G, b, G, G, b, b, b, b]);
+    }
+
+    #[test]
+    fn counts_bound_the_matching_neighbours() {
+        // Red cells see 2 and 2 green neighbours at the bottom, 4 in the middle, 1 and 3 on top.
+        let grid = vec![R, G, R, G, R, G, R, R, G];
+        let b = Tile::Blue as u8;
+        let counted = |count| {
+            let rule = Rule {
+                count,
+                ..rule([Some(Tile::Green), None], Tile::Blue)
+            };
+            step(&grid, SQUARE, &RuleSet::from([(Tile::Red, vec![rule])]))
+        };
+        assert_eq!(counted(Count::exactly(2)), vec![b, G, b, G, R, G, R, R, G]);
+        assert_eq!(counted(Count::at_least(3)), vec![R, G, R, G, b, G, R, b, G]);
+        assert_eq!(
+            counted(Count::between(1, 1)),
+            vec![R, G, R, G, R, G, b, R, G]
+        );
+    }
+
+    #[test]
+    fn combinators_join_the_tile_checks() {
+        // Only the top left cell touches both green and blue.
+        let b = Tile::Blue as u8;
+        let y = Tile::Yellow as u8;
+        let grid = vec![R, R, R, G, R, R, R, b, R];
+        let combined = |combinator| {
+            let rule = Rule {
+                combinator,
+                ..rule([Some(Tile::Green), Some(Tile::Blue)], Tile::Yellow)
+            };
+            step(&grid, SQUARE, &RuleSet::from([(Tile::Red, vec![rule])]))
+        };
+        assert_eq!(combined(Combinator::AllOf), vec![R, R, R, G, y, R, y, b, R]);
+        assert_eq!(combined(Combinator::AnyOf), vec![y, y, R, G, y, y, y, b, y]);
+        assert_eq!(
+            combined(Combinator::NoneOf),
+            vec![R, R, y, G, R, R, R, b, R]
+        );
+    }
+
+    #[test]
+    fn chances_follow_the_seed() {
+        let layout = Layout::new(8, 8);
+        let grid = vec![R; layout.len()];
+        let rules = |chance| {
+            let rule = Rule {
+                chance,
+                ..rule([Some(Tile::Red), None], Tile::Green)
+            };
+            RuleSet::from([(Tile::Red, vec![rule])])
+        };
+        let seeded = |chance, seed| {
+            step_seeded(
+                &grid,
+                layout,
+                &rules(chance),
+                &mut StdRng::seed_from_u64(seed),
+            )
+        };
+        assert_eq!(seeded(0, 1), grid);
+        assert_eq!(seeded(Rule::ALWAYS, 1), vec![G; layout.len()]);
+        assert_eq!(seeded(50, 1), seeded(50, 1));
+        assert_ne!(seeded(50, 1), seeded(50, 2));
+        let changed = seeded(50, 1).iter().filter(|&&tile| tile == G).count();
+        assert!(0 < changed && changed < layout.len());
+    }
+
+    #[test]
+    fn schedules_change_what_cells_see() {
+        let grid = vec![R, G, R, R];
+        let rules = RuleSet::from([(
+            Tile::Red,
+            vec![rule([Some(Tile::Green), None], Tile::Green)],
+        )]);
+        let layout = |schedule| Layout::new(4, 1).with_schedule(schedule);
+        assert_eq!(
+            step(&grid, layout(Schedule::Sync), &rules),
+            vec![G, G, G, R]
+        );
+        // The last cell sees its neighbour turn green earlier in the same generation.
+        assert_eq!(
+            step(&grid, layout(Schedule::Sequential), &rules),
+            vec![G; 4]
+        );
+        assert_eq!(
+            step(&grid, layout(Schedule::Checkerboard), &rules),
+            vec![G; 4]
+        );
+    }
+
+    #[test]
+    fn phases_cover_part_of_the_board() {
+        let grid = vec![G, R, R, R];
+        let rules = RuleSet::from([(
+            Tile::Red,
+            vec![rule([Some(Tile::Green), None], Tile::Green)],
+        )]);
+        let mut rng = StdRng::seed_from_u64(0);
+        let sequential = SMALL.with_schedule(Schedule::Sequential);
+        assert_eq!(sequential.schedule.phases(sequential), 2);
+        assert_eq!(
+            step_phase(&grid, sequential, &rules, 0, &mut rng),
+            vec![G, G, R, R]
+        );
+        assert_eq!(
+            step_phase(&grid, sequential, &rules, 1, &mut rng),
+            vec![G, R, G, G]
+        );
+        let checkerboard = SMALL.with_schedule(Schedule::Checkerboard);
+        assert_eq!(
+            step_phase(&grid, checkerboard, &rules, 0, &mut rng),
+            vec![G, R, R, G]
+        );
+        assert_eq!(
+            step_phase(&grid, checkerboard, &rules, 1, &mut rng),
+            vec![G, G, G, R]
+        );
+    }
+
+    #[test]
+    fn outer_ring_reaches_two_cells() {
+        let layout = Layout::new(5, 5).with_radius(Radius::Two);
+        let mut grid = vec![R; layout.len()];
+        grid[0] = G;
+        let rule = Rule {
+            mask: std::array::from_fn(|i| i >= 8),
+            ..rule([Some(Tile::Green), None], Tile::Green)
+        };
+        let rules = RuleSet::from([(Tile::Red, vec![rule])]);
+        let turned: Vec<usize> = (0..layout.len())
+            .filter(|&i| step(&grid, layout, &rules)[i] == G)
+            .collect();
+        assert_eq!(turned, vec![0, 2, 7, 10, 11, 12]);
+        // Without the radius the outer ring isn't there to match.
+        assert_eq!(step(&grid, Layout::new(5, 5), &rules), grid);
+    }
+
+    #[test]
+    fn edges_resolve_any_distance_out() {
+        assert_eq!(Boundary::Wrap.resolve(-2, 3), Some(1));
+        assert_eq!(Boundary::Wrap.resolve(4, 3), Some(1));
+        assert_eq!(Boundary::Mirror.resolve(-2, 3), Some(1));
+        assert_eq!(Boundary::Mirror.resolve(4, 3), Some(1));
+        assert_eq!(Boundary::Mirror.resolve(-2, 1), Some(0));
+        assert_eq!(Boundary::Empty.resolve(-2, 3), None);
+    }
+
+    #[test]
+    fn recurrence_tells_fixpoints_from_loops() {
+        let history = [[R, R], [G, R], [G, R]];
+        let mut stable = Recurrence::default();
+        for (generation, grid) in history.iter().enumerate().take(2) {
+            assert_eq!(stable.record(generation, grid, |g| &history[g]), None);
+        }
+        assert_eq!(
+            stable.record(2, &history[2], |g| &history[g]),
+            Some(Settled::Stable { after: 1 })
+        );
+
+        let history = [[R, G], [G, R], [G, G], [G, R]];
+        let mut looping = Recurrence::default();
+        for (generation, grid) in history.iter().enumerate().take(3) {
+            assert_eq!(looping.record(generation, grid, |g| &history[g]), None);
+        }
+        assert_eq!(
+            looping.record(3, &history[3], |g| &history[g]),
+            Some(Settled::Loop { period: 2 })
+        );
+    }
+
+    #[test]
+    fn recurrence_checks_boards_sharing_a_hash() {
+        let history = [[R, R], [G, G], [G, G]];
+        let mut recurrence = Recurrence::default();
+        let board_at = |g: usize| history[g].as_slice();
+        assert_eq!(recurrence.record_hashed(7, 0, &history[0], board_at), None);
+        assert_eq!(recurrence.record_hashed(7, 1, &history[1], board_at), None);
+        assert_eq!(
+            recurrence.record_hashed(7, 2, &history[2], board_at),
+            Some(Settled::Stable { after: 1 })
+        );
+    }
+}
@@ -0,0 +1,694 @@
+//! Searches the rule space for a configuration that turns a puzzle into its goal.
+//! Only a single rule per color is considered, the shape every level allows,
+//! and only the rules' tiles, mask, invert and result: counts, combinators and
+//! chances keep their defaults. Levels that lock parts of their preset rules
+//! aren't searched at all, rather than answered with rules the player can't set.
+//!
+//! Since the player can build more than the search tries, a search coming up
+//! empty proves nothing. A level is only [`Verdict::Unsolvable`] when no color
+//! mapping carries every cell to its goal within the cap, which holds whatever
+//! the rules look at, as long as each color has a single rule to turn it.
+//!
+//! Every color first gets the color it turns into, and only mappings that can
+//! carry each cell to its goal color are kept. The remaining rule choices are
+//! narrowed one generation at a time: rules that fire on exactly the same cells
+//! of the current board are grouped, and one branch is explored per group.
+//! Found rules are replayed through [`run_in_bands`] before being reported, which
+//! steps the board on every core.
+//!
+//! Runs are tried shortest first, but a shorter run may need rules the search
+//! doesn't try, so a solution is only [`Solution::minimal`] when no mapping
+//! could get there in fewer steps.
+//!
+//! The grouping relies on every cell updating at once, so boards with another
+//! [`Schedule`] are left [`Verdict::Undecided`]. So are boards with a
+//! [`Radius`] of two, the conditions only cover the touching cells.
+
+use std::collections::HashMap;
+
+use super::{
+    bitplane::run_in_bands,
+    simulation::{
+        self, ANY, Grid, Layout, Radius, Rule, RuleSet, Schedule, Tile, Topology, color_pool,
+        neighbours,
+    },
+};
+
+/// A rule set that solves a level and the number of steps it needs.
+#[derive(Debug)]
+pub struct Solution {
+    pub rules: RuleSet,
+    pub steps: usize,
+    /// Whether every shorter run was ruled out.
+    pub minimal: bool,
+}
+
+#[derive(Debug)]
+pub enum Verdict {
+    Solved(Solution),
+    /// No rules the player can set reach the goal within the generation cap.
+    Unsolvable,
+    /// The search found nothing within its budget or the part of the rule
+    /// space it covers, or can't search the rules the level allows.
+    Undecided,
+}
+
+/// Looks for the rules that reach `goal` from `puzzle` in the fewest steps,
+/// keeping the history within `max` generations like [`GridIterations`] does.
+/// `budget` caps the number of boards visited or lined up to visit by each of
+/// the two passes: the shortest-first pass, and the one that settles for any
+/// run under the cap.
+/// `presets` and `rules_per_color` are what the level starts the rules with
+/// and how many it allows each color.
+///
+/// [`GridIterations`]: super::logic::GridIterations
+pub fn solve(
+    puzzle: &[u8],
+    goal: &[u8],
+    layout: Layout,
+    max: usize,
+    budget: usize,
+    presets: &RuleSet,
+    rules_per_color: usize,
+) -> Verdict {
+    let pool = color_pool(puzzle, goal);
+    if layout.schedule != Schedule::Sync || layout.radius != Radius::One {
+        return Verdict::Undecided;
+    }
+    if simulation::reached(puzzle, goal) {
+        return Verdict::Solved(Solution {
+            rules: default_rules(&pool),
+            steps: 0,
+            minimal: true,
+        });
+    }
+    // The history holds `max` boards, the starting one included.
+    let most_steps = max.saturating_sub(1);
+    if most_steps == 0 {
+        return Verdict::Unsolvable;
+    }
+    // Locked parts stay whatever the search picks, so its rules may be out of reach.
+    if presets.values().flatten().any(|rule| rule.locked.any()) {
+        return Verdict::Undecided;
+    }
+    let solver = Solver {
+        puzzle,
+        goal,
+        layout,
+        conditions: Condition::all(&pool, layout.topology),
+        mappings: Mapping::all(&pool, puzzle, goal),
+        pool,
+    };
+    // The fewest steps any mapping needs. With more rules to a color, a color
+    // can turn into several others and the mappings bound nothing.
+    let fewest = match solver.mappings.first() {
+        _ if rules_per_color > 1 => None,
+        Some(mapping) => Some(mapping.lower_bound.max(1)),
+        None => return Verdict::Unsolvable,
+    };
+    if fewest.is_some_and(|fewest| fewest > most_steps) {
+        return Verdict::Unsolvable;
+    }
+    let minimal = |solution: Solution| Solution {
+        minimal: fewest.is_some_and(|fewest| solution.steps <= fewest),
+        ..solution
+    };
+
+    let mut remaining = budget;
+    for steps in 1..max {
+        if let Some(solution) = solver.attempt(steps, &mut remaining) {
+            return Verdict::Solved(minimal(solution));
+        }
+        if remaining == 0 {
+            break;
+        }
+    }
+    if remaining > 0 {
+        return Verdict::Undecided;
+    }
+
+    let mut remaining = budget;
+    match solver.attempt(most_steps, &mut remaining) {
+        Some(solution) => Verdict::Solved(minimal(solution)),
+        None => Verdict::Undecided,
+    }
+}
+
+struct Solver<'a> {
+    puzzle: &'a [u8],
+    goal: &'a [u8],
+    layout: Layout,
+    pool: Vec<Tile>,
+    conditions: Vec<Condition>,
+    mappings: Vec<Mapping>,
+}
+
+impl Solver<'_> {
+    /// Searches every mapping for rules that reach the goal within `steps`.
+    fn attempt(&self, steps: usize, budget: &mut usize) -> Option<Solution> {
+        for mapping in self.mappings.iter().filter(|m| m.lower_bound <= steps) {
+            let mut search = Search {
+                goal: self.goal,
+                layout: self.layout,
+                pool: &self.pool,
+                mapping,
+                conditions: &self.conditions,
+                path: vec![self.puzzle.to_vec()],
+                budget: *budget,
+            };
+            let candidates: Vec<Vec<u32>> = mapping
+                .results
+                .iter()
+                .map(|result| match result {
+                    Some(_) => (0..self.conditions.len() as u32).collect(),
+                    None => vec![],
+                })
+                .collect();
+            let found = search.step(self.puzzle, &candidates, steps);
+            *budget = search.budget;
+            if let Some(chosen) = found {
+                let mut rules = default_rules(&self.pool);
+                for (i, (&tile, result)) in self.pool.iter().zip(&mapping.results).enumerate() {
+                    if let (Some(result), Some(&index)) = (result, chosen[i].first()) {
+                        let rule = self.conditions[index as usize].to_rule(self.pool[*result]);
+                        rules.insert(tile, vec![rule]);
+                    }
+                }
+                let history = run_in_bands(self.puzzle, self.layout, &rules, steps + 1);
+                if let Some(steps) = history
+                    .iter()
+                    .position(|grid| simulation::reached(grid, self.goal))
+                {
+                    return Some(Solution {
+                        rules,
+                        steps,
+                        minimal: false,
+                    });
+                }
+            }
+            if *budget == 0 {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+fn default_rules(pool: &[Tile]) -> RuleSet {
+    pool.iter()
+        .map(|&tile| (tile, vec![Rule::default()]))
+        .collect()
+}
+
+/// The part of a [`Rule`] that decides whether it fires.
+#[derive(Clone, Copy)]
+struct Condition {
+    tiles: [Option<Tile>; 2],
+    mask: u8,
+    invert: bool,
+}
+
+impl Condition {
+    /// Every distinct condition the rule editor can build from `pool`, keeping
+    /// the default count of one or more, the any-of combinator and no chance
+    /// of failing. Masks only cover the directions `topology` has, the others
+    /// never matter.
+    fn all(pool: &[Tile], topology: Topology) -> Vec<Condition> {
+        let directions = topology
+            .directions()
+            .iter()
+            .enumerate()
+            .fold(0u8, |bits, (i, &exists)| bits | (exists as u8) << i);
+        let mut tile_sets = vec![];
+        for (i, &first) in pool.iter().enumerate() {
+            tile_sets.push([Some(first), None]);
+            for &second in &pool[i + 1..] {
+                tile_sets.push([Some(first), Some(second)]);
+            }
+        }
+        let mut conditions = vec![];
+        for tiles in tile_sets {
+            for mask in (0..=u8::MAX).filter(|mask| mask & !directions == 0) {
+                for invert in [false, true] {
+                    conditions.push(Condition {
+                        tiles,
+                        mask,
+                        invert,
+                    });
+                }
+            }
+        }
+        conditions
+    }
+
+    /// `directions[tile]` holds one bit per neighbour of that color, in mask order.
+    fn fires(&self, directions: &[u8; 9]) -> bool {
+        self.tiles
+            .iter()
+            .flatten()
+            .any(|&tile| (directions[tile as usize] & self.mask != 0) ^ self.invert)
+    }
+
+    fn to_rule(self, result: Tile) -> Rule {
+        Rule {
+            tiles: self.tiles,
+            invert: self.invert,
+            mask: std::array::from_fn(|i| i < 8 && self.mask & (1 << i) != 0),
+            result: Some(result),
+            ..Rule::default()
+        }
+    }
+}
+
+/// Which color each color of the pool turns into, if any.
+struct Mapping {
+    /// Indexed by position in the color pool, like everything else here.
+    results: Vec<Option<usize>>,
+    /// `distance[from][to]` is the number of firings needed to get from one
+    /// pool color to another, `usize::MAX` if it can't be done.
+    distance: Vec<Vec<usize>>,
+    /// The fewest steps any level cell needs to reach its goal color.
+    lower_bound: usize,
+}
+
+/// Whether a cell holding `from` has nothing to reach: any color satisfies its
+/// goal, or it is a wall or hole that never changes.
+fn free(from: u8, to: u8) -> bool {
+    to == ANY || simulation::is_fixed(from)
+}
+
+impl Mapping {
+    /// Every mapping under which each cell of `puzzle` can reach its color in `goal`.
+    fn all(pool: &[Tile], puzzle: &[u8], goal: &[u8]) -> Vec<Mapping> {
+        let index = |value: u8| {
+            pool.iter()
+                .position(|&tile| tile == Tile::from_u8(value))
+                .unwrap()
+        };
+        let mut required = vec![vec![false; pool.len()]; pool.len()];
+        for (&from, &to) in puzzle
+            .iter()
+            .zip(goal)
+            .filter(|&(&from, &to)| !free(from, to))
+        {
+            required[index(from)][index(to)] = true;
+        }
+        let mut mappings = vec![];
+        let mut results = vec![None; pool.len()];
+        Self::assign(pool, &required, 0, &mut results, &mut mappings);
+        mappings.sort_by_key(|mapping| mapping.lower_bound);
+        mappings
+    }
+
+    fn assign(
+        pool: &[Tile],
+        required: &[Vec<bool>],
+        color: usize,
+        results: &mut Vec<Option<usize>>,
+        mappings: &mut Vec<Mapping>,
+    ) {
+        if color == pool.len() {
+            if let Some(mapping) = Self::new(pool, required, results) {
+                mappings.push(mapping);
+            }
+            return;
+        }
+        // A color that never fires can only be right if all of its cells already are.
+        let must_change = (0..pool.len()).any(|to| to != color && required[color][to]);
+        if !must_change {
+            results[color] = None;
+            Self::assign(pool, required, color + 1, results, mappings);
+        }
+        for result in (0..pool.len()).filter(|&result| result != color) {
+            results[color] = Some(result);
+            Self::assign(pool, required, color + 1, results, mappings);
+        }
+    }
+
+    fn new(pool: &[Tile], required: &[Vec<bool>], results: &[Option<usize>]) -> Option<Mapping> {
+        let mut distance = vec![vec![usize::MAX; pool.len()]; pool.len()];
+        for (from, row) in distance.iter_mut().enumerate() {
+            let mut current = from;
+            for steps in 0..pool.len() {
+                if row[current] != usize::MAX {
+                    break;
+                }
+                row[current] = steps;
+                match results[current] {
+                    Some(next) => current = next,
+                    None => break,
+                }
+            }
+        }
+        let mut lower_bound = 0;
+        for (from, row) in required.iter().enumerate() {
+            for (to, &needed) in row.iter().enumerate() {
+                if needed {
+                    if distance[from][to] == usize::MAX {
+                        return None;
+                    }
+                    lower_bound = lower_bound.max(distance[from][to]);
+                }
+            }
+        }
+        Some(Mapping {
+            results: results.to_vec(),
+            distance,
+            lower_bound,
+        })
+    }
+}
+
+struct Search<'a> {
+    goal: &'a [u8],
+    layout: Layout,
+    pool: &'a [Tile],
+    mapping: &'a Mapping,
+    conditions: &'a [Condition],
+    /// Boards already visited on the way to the current one.
+    path: Vec<Grid>,
+    /// Boards left to visit before giving up.
+    budget: usize,
+}
+
+impl Search<'_> {
+    fn index(&self, value: u8) -> usize {
+        let tile = Tile::from_u8(value);
+        self.pool.iter().position(|&t| t == tile).unwrap()
+    }
+
+    /// Total number of firings every cell still needs to reach its goal color.
+    fn distance_to_goal(&self, grid: &[u8]) -> usize {
+        grid.iter()
+            .zip(self.goal)
+            .filter(|&(&from, &to)| !free(from, to))
+            .map(|(&from, &to)| self.mapping.distance[self.index(from)][self.index(to)])
+            .sum()
+    }
+
+    /// Returns the surviving candidates per color once `grid` reaches the goal
+    /// within `remaining` steps.
+    fn step(
+        &mut self,
+        grid: &[u8],
+        candidates: &[Vec<u32>],
+        remaining: usize,
+    ) -> Option<Vec<Vec<u32>>> {
+        if simulation::reached(grid, self.goal) {
+            return Some(candidates.to_vec());
+        }
+        if remaining == 0 || self.budget == 0 {
+            return None;
+        }
+        self.budget -= 1;
+        let distance = &self.mapping.distance;
+        let mut can_fire = vec![false; grid.len()];
+        let mut can_stay = vec![false; grid.len()];
+        for (i, (&from, &to)) in grid.iter().zip(self.goal).enumerate() {
+            if free(from, to) {
+                can_stay[i] = true;
+                can_fire[i] = true;
+                continue;
+            }
+            let (from, to) = (self.index(from), self.index(to));
+            can_stay[i] = distance[from][to] < remaining;
+            can_fire[i] =
+                self.mapping.results[from].is_some_and(|next| distance[next][to] < remaining);
+            if !can_stay[i] && !can_fire[i] {
+                return None;
+            }
+        }
+
+        // Group each color's candidates by the cells they fire on.
+        let mut groups: Vec<Vec<(Vec<usize>, Vec<u32>)>> = vec![];
+        for (color, tile) in self.pool.iter().enumerate() {
+            if self.mapping.results[color].is_none() {
+                groups.push(vec![(vec![], vec![])]);
+                continue;
+            }
+            let cells: Vec<(usize, [u8; 9])> = grid
+                .iter()
+                .enumerate()
+                .filter(|&(_, &value)| Tile::from_u8(value) == *tile)
+                .map(|(i, _)| {
+                    let mut directions = [0; 9];
+                    for (direction, neighbour) in neighbours(grid, self.layout, i)
+                        .into_iter()
+                        .take(8)
+                        .enumerate()
+                    {
+                        directions[neighbour as usize] |= 1 << direction;
+                    }
+                    (i, directions)
+                })
+                .collect();
+            let mut by_pattern: HashMap<Vec<usize>, usize> = HashMap::new();
+            let mut color_groups: Vec<(Vec<usize>, Vec<u32>)> = vec![];
+            'candidates: for &candidate in &candidates[color] {
+                let condition = &self.conditions[candidate as usize];
+                let mut fired = vec![];
+                for &(i, directions) in &cells {
+                    let fires = condition.fires(&directions);
+                    if (fires && !can_fire[i]) || (!fires && !can_stay[i]) {
+                        continue 'candidates;
+                    }
+                    if fires {
+                        fired.push(i);
+                    }
+                }
+                match by_pattern.get(&fired) {
+                    Some(&group) => color_groups[group].1.push(candidate),
+                    None => {
+                        by_pattern.insert(fired.clone(), color_groups.len());
+                        color_groups.push((fired, vec![candidate]));
+                    }
+                }
+            }
+            if color_groups.is_empty() {
+                return None;
+            }
+            groups.push(color_groups);
+        }
+
+        // Every board one step on is held at once to sort them, so they come
+        // out of the budget up front. Boards with many colors can otherwise
+        // have more combinations of groups than fit in memory.
+        let combinations = groups
+            .iter()
+            .try_fold(1usize, |product, color| product.checked_mul(color.len()))
+            .filter(|&combinations| combinations <= self.budget);
+        let Some(combinations) = combinations else {
+            self.budget = 0;
+            return None;
+        };
+        self.budget -= combinations;
+
+        // Try the boards closest to the goal first.
+        let mut children = vec![];
+        let mut choice = vec![0; groups.len()];
+        loop {
+            let mut next = grid.to_vec();
+            for (color, &group) in choice.iter().enumerate() {
+                if let Some(result) = self.mapping.results[color] {
+                    for &i in &groups[color][group].0 {
+                        next[i] = self.pool[result] as u8;
+                    }
+                }
+            }
+            // Every candidate left behaves the same on boards already seen, so a
+            // repeated board means the run is stuck in a loop.
+            if !self.path.contains(&next) {
+                children.push((self.distance_to_goal(&next), next, choice.clone()));
+            }
+            // Advance to the next combination of groups.
+            let mut color = 0;
+            while color < choice.len() {
+                choice[color] += 1;
+                if choice[color] < groups[color].len() {
+                    break;
+                }
+                choice[color] = 0;
+                color += 1;
+            }
+            if color == choice.len() {
+                break;
+            }
+        }
+        children.sort_by_key(|(distance, _, _)| *distance);
+
+        for (_, next, choice) in children {
+            let narrowed: Vec<Vec<u32>> = choice
+                .iter()
+                .enumerate()
+                .map(|(color, &group)| groups[color][group].1.clone())
+                .collect();
+            self.path.push(next);
+            let found = self.step(
+                self.path.last().unwrap().clone().as_slice(),
+                &narrowed,
+                remaining - 1,
+            );
+            self.path.pop();
+            if found.is_some() || self.budget == 0 {
+                return found;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::simulation::Locks;
+
+    const R: u8 = Tile::Red as u8;
+    const G: u8 = Tile::Green as u8;
+    const B: u8 = Tile::Blue as u8;
+
+    #[test]
+    fn solved_board_needs_no_steps() {
+        let grid = vec![R, G, G, R];
+        let Verdict::Solved(solution) = solve(
+            &grid,
+            &grid,
+            Layout::new(2, 2),
+            50,
+            1000,
+            &RuleSet::new(),
+            1,
+        ) else {
+            panic!("a solved board must stay solved");
+        };
+        assert_eq!(solution.steps, 0);
+    }
+
+    #[test]
+    fn finds_the_shortest_run() {
+        let puzzle = vec![G, R, R, R, R, R, R, R, R];
+        let goal = vec![G; 9];
+        let Verdict::Solved(solution) = solve(
+            &puzzle,
+            &goal,
+            Layout::new(3, 3),
+            50,
+            100_000,
+            &RuleSet::new(),
+            1,
+        ) else {
+            panic!("filling the board is a single step");
+        };
+        assert_eq!(solution.steps, 1);
+        assert!(solution.minimal);
+        assert_eq!(
+            simulation::step(&puzzle, Layout::new(3, 3), &solution.rules),
+            goal
+        );
+    }
+
+    #[test]
+    fn any_color_cells_are_left_alone() {
+        // Turning the corner green would take a second step, but it doesn't matter.
+        let puzzle = vec![G, R, R, R, R, R, R, R, R];
+        let goal = vec![G, G, ANY, G, G, ANY, ANY, ANY, ANY];
+        let Verdict::Solved(solution) = solve(
+            &puzzle,
+            &goal,
+            Layout::new(3, 3),
+            50,
+            100_000,
+            &RuleSet::new(),
+            1,
+        ) else {
+            panic!("the cells next to the green one turn in a single step");
+        };
+        assert_eq!(solution.steps, 1);
+        let next = simulation::step(&puzzle, Layout::new(3, 3), &solution.rules);
+        assert!(simulation::reached(&next, &goal));
+    }
+
+    #[test]
+    fn walls_have_no_goal_to_reach() {
+        let puzzle = vec![R, simulation::WALL, R, R];
+        let Verdict::Solved(solution) = solve(
+            &puzzle,
+            &[G; 4],
+            Layout::new(2, 2),
+            50,
+            100_000,
+            &RuleSet::new(),
+            1,
+        ) else {
+            panic!("every red cell can turn green in a single step");
+        };
+        assert_eq!(solution.steps, 1);
+        let next = simulation::step(&puzzle, Layout::new(2, 2), &solution.rules);
+        assert_eq!(next, vec![G, simulation::WALL, G, G]);
+    }
+
+    #[test]
+    fn runs_must_fit_under_the_cap() {
+        assert!(matches!(
+            solve(&[R], &[G], Layout::new(1, 1), 1, 1000, &RuleSet::new(), 1),
+            Verdict::Unsolvable
+        ));
+        // A history of one board leaves no step to take, however many rules.
+        assert!(matches!(
+            solve(&[R], &[G], Layout::new(1, 1), 1, 1000, &RuleSet::new(), 2),
+            Verdict::Unsolvable
+        ));
+    }
+
+    #[test]
+    fn only_proofs_make_it_unsolvable() {
+        // One rule turns red into a single color, so one of the cells needs a
+        // second step. Two rules could split them in one.
+        let layout = Layout::new(2, 1);
+        assert!(matches!(
+            solve(&[R, R], &[G, B], layout, 2, 1000, &RuleSet::new(), 1),
+            Verdict::Unsolvable
+        ));
+        assert!(matches!(
+            solve(&[R, R], &[G, B], layout, 2, 1000, &RuleSet::new(), 2),
+            Verdict::Undecided
+        ));
+        // Running out of budget settles nothing either.
+        let puzzle = vec![G, R, R, R, R, R, R, R, R];
+        assert!(matches!(
+            solve(
+                &puzzle,
+                &[G; 9],
+                Layout::new(3, 3),
+                50,
+                0,
+                &RuleSet::new(),
+                1
+            ),
+            Verdict::Undecided
+        ));
+        // A locked preset would keep the rule the search picks from being set.
+        let presets = RuleSet::from([(
+            Tile::Red,
+            vec![Rule {
+                locked: Locks {
+                    result: true,
+                    ..Locks::default()
+                },
+                ..Rule::default()
+            }],
+        )]);
+        let puzzle = vec![G, R, R, R, R, R, R, R, R];
+        assert!(matches!(
+            solve(
+                &puzzle,
+                &[G; 9],
+                Layout::new(3, 3),
+                50,
+                100_000,
+                &presets,
+                1
+            ),
+            Verdict::Undecided
+        ));
+    }
+}
@@ -0,0 +1,296 @@
+//! Undo and redo for rule editing.
+//!
+//! The rule controls change [`PlayerRules`] in place. The rules are remembered
+//! before they run and compared after, and the colors whose rules changed make
+//! up one entry on the undo stack, with their rules from before and after.
+
+use bevy::prelude::*;
+
+use super::{
+    interface::MousePainting,
+    logic::{DisableControls, PlayerRules},
+    simulation::{Rule, RuleSet, Tile},
+};
+use crate::theme::widget::{self, BUTTON_SIZE_ALT};
+
+/// The rules of one color before and after an edit.
+#[derive(Debug, Clone)]
+struct ColorEdit {
+    tile: Tile,
+    before: Vec<Rule>,
+    after: Vec<Rule>,
+}
+
+/// The undo and redo stacks of the rules being edited, emptied whenever a
+/// level or the sandbox hands out new ones.
+#[derive(Resource, Default)]
+pub struct RuleHistory {
+    undo: Vec<Vec<ColorEdit>>,
+    redo: Vec<Vec<ColorEdit>>,
+    /// The rules as they were before this frame's edits.
+    before: RuleSet,
+    /// Whether the last entry is a mask stroke still being painted, which
+    /// every cell the drag passes over joins.
+    stroke: bool,
+}
+
+impl RuleHistory {
+    /// Records the difference between the rules remembered and `rules` as a
+    /// new entry, or as part of the last one while `painting` continues it.
+    fn record(&mut self, rules: &RuleSet, painting: bool) {
+        let mut tiles: Vec<Tile> = self.before.keys().chain(rules.keys()).copied().collect();
+        tiles.sort();
+        tiles.dedup();
+        let edits: Vec<ColorEdit> = tiles
+            .into_iter()
+            .map(|tile| ColorEdit {
+                tile,
+                before: settled(self.before.get(&tile)),
+                after: settled(rules.get(&tile)),
+            })
+            .filter(|edit| edit.before != edit.after)
+            .collect();
+        if edits.is_empty() {
+            self.stroke &= painting;
+            return;
+        }
+        self.redo.clear();
+        match self.undo.last_mut() {
+            Some(entry) if self.stroke && painting => {
+                for edit in edits {
+                    match entry.iter_mut().find(|earlier| earlier.tile == edit.tile) {
+                        Some(earlier) => earlier.after = edit.after,
+                        None => entry.push(edit),
+                    }
+                }
+            }
+            _ => self.undo.push(edits),
+        }
+        self.stroke = painting;
+    }
+
+    /// Takes back the last entry.
+    pub fn undo(&mut self, rules: &mut RuleSet) {
+        let Some(entry) = self.undo.pop() else {
+            return;
+        };
+        for edit in &entry {
+            restore(rules, edit.tile, &edit.before);
+        }
+        self.redo.push(entry);
+        self.stroke = false;
+    }
+
+    /// Makes the last entry taken back again.
+    pub fn redo(&mut self, rules: &mut RuleSet) {
+        let Some(entry) = self.redo.pop() else {
+            return;
+        };
+        for edit in &entry {
+            restore(rules, edit.tile, &edit.after);
+        }
+        self.undo.push(entry);
+        self.stroke = false;
+    }
+}
+
+/// A color's rules without the editor's drag bookkeeping, so only real
+/// changes tell entries apart.
+fn settled(rules: Option<&Vec<Rule>>) -> Vec<Rule> {
+    let mut rules = rules.cloned().unwrap_or_default();
+    for rule in &mut rules {
+        rule.changed.fill(false);
+    }
+    rules
+}
+
+/// Puts back the rules a color had, dropping the color if it had none.
+fn restore(rules: &mut RuleSet, tile: Tile, list: &[Rule]) {
+    if list.is_empty() {
+        rules.remove(&tile);
+    } else {
+        rules.insert(tile, list.to_vec());
+    }
+}
+
+/// Whether either Ctrl key, or Cmd on a Mac, is held.
+pub fn ctrl_held(keys: &ButtonInput<KeyCode>) -> bool {
+    keys.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ])
+}
+
+/// Runs before the rule controls, to compare their edits against.
+pub fn remember_rules(rules: Res<PlayerRules>, mut history: ResMut<RuleHistory>) {
+    history.before.clone_from(&rules.rules);
+}
+
+/// Runs after the rule controls, turning whatever they changed into an entry.
+pub fn record_rule_edits(
+    rules: Res<PlayerRules>,
+    painting: Option<Res<MousePainting>>,
+    mut history: ResMut<RuleHistory>,
+) {
+    history.record(&rules.rules, painting.is_some());
+}
+
+/// Ctrl+Z takes back the last edit, Ctrl+Shift+Z makes it again.
+pub fn handle_undo_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut rules: ResMut<PlayerRules>,
+    mut history: ResMut<RuleHistory>,
+) {
+    if !ctrl_held(&keys) || !keys.just_pressed(KeyCode::KeyZ) {
+        return;
+    }
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        history.redo(&mut rules.rules);
+    } else {
+        history.undo(&mut rules.rules);
+    }
+}
+
+/// Undo and redo buttons for the sidebar.
+pub fn undo_buttons() -> impl Bundle {
+    (
+        Name::new("Undo Buttons"),
+        Node {
+            display: Display::Flex,
+            flex_direction: FlexDirection::Row,
+            column_gap: Val::Px(8.0),
+            ..default()
+        },
+        children![
+            widget::button_custom("", undo, None, Some(BUTTON_SIZE_ALT)),
+            widget::button_custom("", redo, None, Some(BUTTON_SIZE_ALT)),
+        ],
+    )
+}
+
+fn undo(
+    _: Trigger<Pointer<Click>>,
+    locked: Option<Res<DisableControls>>,
+    mut rules: ResMut<PlayerRules>,
+    mut history: ResMut<RuleHistory>,
+) {
+    if locked.is_none() {
+        history.undo(&mut rules.rules);
+    }
+}
+
+fn redo(
+    _: Trigger<Pointer<Click>>,
+    locked: Option<Res<DisableControls>>,
+    mut rules: ResMut<PlayerRules>,
+    mut history: ResMut<RuleHistory>,
+) {
+    if locked.is_none() {
+        history.redo(&mut rules.rules);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(result: Tile) -> Rule {
+        Rule {
+            tiles: [Some(Tile::Red), None],
+            result: Some(result),
+            ..Rule::default()
+        }
+    }
+
+    /// Remembers `before`, then records the change to `after`.
+    fn edit(history: &mut RuleHistory, before: &RuleSet, after: &RuleSet, painting: bool) {
+        history.before.clone_from(before);
+        history.record(after, painting);
+    }
+
+    #[test]
+    fn an_edit_becomes_one_entry() {
+        let mut history = RuleHistory::default();
+        let before = RuleSet::from([(Tile::Red, vec![rule(Tile::Green)])]);
+        let after = RuleSet::from([(Tile::Red, vec![rule(Tile::Blue)])]);
+        edit(&mut history, &before, &before, false);
+        assert!(history.undo.is_empty());
+        edit(&mut history, &before, &after, false);
+        assert_eq!(history.undo.len(), 1);
+        assert_eq!(history.undo[0][0].tile, Tile::Red);
+    }
+
+    #[test]
+    fn drag_bookkeeping_is_not_an_edit() {
+        let mut history = RuleHistory::default();
+        let before = RuleSet::from([(Tile::Red, vec![rule(Tile::Green)])]);
+        let mut after = before.clone();
+        after.get_mut(&Tile::Red).unwrap()[0].changed[0] = true;
+        edit(&mut history, &before, &after, true);
+        assert!(history.undo.is_empty());
+    }
+
+    #[test]
+    fn undo_then_redo_restores_both_sides() {
+        let mut history = RuleHistory::default();
+        let before = RuleSet::from([(Tile::Red, vec![rule(Tile::Green)])]);
+        let after = RuleSet::from([
+            (Tile::Red, vec![rule(Tile::Blue)]),
+            (Tile::Green, vec![rule(Tile::Red)]),
+        ]);
+        edit(&mut history, &before, &after, false);
+
+        let mut rules = after.clone();
+        history.undo(&mut rules);
+        assert_eq!(rules, before);
+        assert!(history.undo.is_empty());
+
+        history.redo(&mut rules);
+        assert_eq!(rules, after);
+        assert!(history.redo.is_empty());
+    }
+
+    #[test]
+    fn a_new_edit_clears_redo() {
+        let mut history = RuleHistory::default();
+        let first = RuleSet::from([(Tile::Red, vec![rule(Tile::Green)])]);
+        let second = RuleSet::from([(Tile::Red, vec![rule(Tile::Blue)])]);
+        edit(&mut history, &RuleSet::new(), &first, false);
+        edit(&mut history, &first, &second, false);
+
+        let mut rules = second.clone();
+        history.undo(&mut rules);
+        assert_eq!(history.redo.len(), 1);
+
+        edit(&mut history, &rules, &second, false);
+        assert!(history.redo.is_empty());
+        assert_eq!(history.undo.len(), 2);
+    }
+
+    #[test]
+    fn a_paint_stroke_is_one_entry() {
+        let mut history = RuleHistory::default();
+        let mut rules = RuleSet::from([(Tile::Red, vec![rule(Tile::Green)])]);
+        let start = rules.clone();
+        for i in 0..3 {
+            let before = rules.clone();
+            let mask = &mut rules.get_mut(&Tile::Red).unwrap()[0].mask;
+            mask[i] = false;
+            edit(&mut history, &before, &rules, true);
+        }
+        assert_eq!(history.undo.len(), 1);
+
+        // Letting go ends the stroke, so the next one starts a new entry.
+        edit(&mut history, &rules, &rules, false);
+        let before = rules.clone();
+        rules.get_mut(&Tile::Red).unwrap()[0].mask[5] = false;
+        edit(&mut history, &before, &rules, true);
+        assert_eq!(history.undo.len(), 2);
+
+        history.undo(&mut rules);
+        history.undo(&mut rules);
+        assert_eq!(rules, start);
+    }
+}
@@ -5,7 +5,7 @@ use bevy::prelude::*;
 use crate::{
     asset_tracking::ResourceHandles,
     menus::Menu,
-    screens::Screen,
+    screens::{AfterLoading, Screen},
     theme::widget::{self, BUTTON_COLORS_ALT},
 };
 
@@ -38,10 +38,11 @@ fn spawn_main_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
             },
             widget::button_custom(
                 "Play",
-                enter_loading_or_gameplay_screen,
+                enter_pack_select_screen,
                 Some(BUTTON_COLORS_ALT),
                 None
             ),
+            widget::button("Sandbox", enter_sandbox_screen),
             widget::button("Settings", open_settings_menu),
             widget::button("Credits", open_credits_menu),
             widget::button("Exit", exit_app),
@@ -66,24 +67,41 @@ fn spawn_main_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
             },
             widget::button_custom(
                 "Play",
-                enter_loading_or_gameplay_screen,
+                enter_pack_select_screen,
                 Some(BUTTON_COLORS_ALT),
                 None
             ),
+            widget::button("Sandbox", enter_sandbox_screen),
             widget::button("Settings", open_settings_menu),
             widget::button("Credits", open_credits_menu),
         ],
     ));
 }
 
-fn enter_loading_or_gameplay_screen(
+fn enter_pack_select_screen(
     _: Trigger<Pointer<Click>>,
+    mut commands: Commands,
     resource_handles: Res<ResourceHandles>,
     mut next_screen: ResMut<NextState<Screen>>,
 ) {
     if resource_handles.is_all_done() {
-        next_screen.set(Screen::Gameplay);
+        next_screen.set(Screen::PackSelect);
     } else {
+        commands.insert_resource(AfterLoading(Screen::PackSelect));
+        next_screen.set(Screen::Loading);
+    }
+}
+
+fn enter_sandbox_screen(
+    _: Trigger<Pointer<Click>>,
+    mut commands: Commands,
+    resource_handles: Res<ResourceHandles>,
+    mut next_screen: ResMut<NextState<Screen>>,
+) {
+    if resource_handles.is_all_done() {
+        next_screen.set(Screen::Sandbox);
+    } else {
+        commands.insert_resource(AfterLoading(Screen::Sandbox));
         next_screen.set(Screen::Loading);
     }
 }
@@ -4,7 +4,7 @@
 
 use bevy::{audio::Volume, input::common_conditions::input_just_pressed, prelude::*, ui::Val::*};
 
-use crate::{menus::Menu, screens::Screen, theme::prelude::*};
+use crate::{game::logic::ParallelThreshold, menus::Menu, screens::Screen, theme::prelude::*};
 
 pub(super) fn plugin(app: &mut App) {
     app.add_systems(OnEnter(Menu::Settings), spawn_settings_menu);
@@ -14,9 +14,11 @@ pub(super) fn plugin(app: &mut App) {
     );
 
     app.register_type::<GlobalVolumeLabel>();
+    app.register_type::<ParallelThresholdLabel>();
     app.add_systems(
         Update,
-        update_global_volume_label.run_if(in_state(Menu::Settings)),
+        (update_global_volume_label, update_parallel_threshold_label)
+            .run_if(in_state(Menu::Settings)),
     );
 }
 
@@ -53,6 +55,14 @@ fn settings_grid() -> impl Bundle {
                 }
             ),
             global_volume_widget(),
+            (
+                widget::label("Parallel Stepping"),
+                Node {
+                    justify_self: JustifySelf::End,
+                    ..default()
+                }
+            ),
+            parallel_threshold_widget(),
         ],
     )
 }
@@ -106,6 +116,65 @@ fn update_global_volume_label(
     label.0 = format!("{percent:3.0}%");
 }
 
+fn parallel_threshold_widget() -> impl Bundle {
+    (
+        Name::new("Parallel Threshold Widget"),
+        Node {
+            justify_self: JustifySelf::Start,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        children![
+            widget::button_small("-", lower_parallel_threshold),
+            (
+                Name::new("Current Threshold"),
+                Node {
+                    padding: UiRect::horizontal(Px(10.0)),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                children![(widget::label(""), ParallelThresholdLabel)],
+            ),
+            widget::button_small("+", raise_parallel_threshold),
+        ],
+    )
+}
+
+fn lower_parallel_threshold(_: Trigger<Pointer<Click>>, mut threshold: ResMut<ParallelThreshold>) {
+    let choices = ParallelThreshold::CHOICES;
+    threshold.0 = choices
+        .into_iter()
+        .rev()
+        .find(|&choice| choice < threshold.0)
+        .unwrap_or(choices[0]);
+}
+
+fn raise_parallel_threshold(_: Trigger<Pointer<Click>>, mut threshold: ResMut<ParallelThreshold>) {
+    let choices = ParallelThreshold::CHOICES;
+    threshold.0 = choices
+        .into_iter()
+        .find(|&choice| choice > threshold.0)
+        .unwrap_or(choices[choices.len() - 1]);
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+struct ParallelThresholdLabel;
+
+fn update_parallel_threshold_label(
+    threshold: Res<ParallelThreshold>,
+    mut label: Single<&mut Text, With<ParallelThresholdLabel>>,
+) {
+    label.0 = match threshold.0 {
+        0 => "Always".to_string(),
+        usize::MAX => "Never".to_string(),
+        cells => {
+            let side = (cells as f64).sqrt() as usize;
+            format!("From {side}x{side}")
+        }
+    };
+}
+
 fn go_back_on_click(
     _: Trigger<Pointer<Click>>,
     screen: Res<State<Screen>>,
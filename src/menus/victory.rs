@@ -2,7 +2,11 @@
 
 use bevy::{input::common_conditions::input_just_pressed, prelude::*};
 
-use crate::{game::interface::go_next_level, menus::Menu, theme::widget};
+use crate::{
+    game::{interface::go_next_level, level::CurrentLevel, logic::GridIterations},
+    menus::Menu,
+    theme::widget,
+};
 use bevy_shuffle_bag::ShuffleBag;
 
 pub(super) fn plugin(app: &mut App) {
@@ -13,7 +17,11 @@ pub(super) fn plugin(app: &mut App) {
     );
 }
 
-fn spawn_victory_menu(mut commands: Commands) {
+fn spawn_victory_menu(
+    mut commands: Commands,
+    grid: Res<GridIterations>,
+    current_level: CurrentLevel,
+) {
     let mut rng = rand::thread_rng();
     let mut victory_messages = ShuffleBag::try_new(
         [
@@ -35,6 +43,10 @@ fn spawn_victory_menu(mut commands: Commands) {
         StateScoped(Menu::Victory),
         children![
             widget::header(*victory_messages.pick(&mut rng)),
+            widget::label(steps_taken(
+                grid.generations() - 1,
+                current_level.get().and_then(|level| level.par)
+            )),
             widget::header(" "), // just a gap
             widget::button("Go Back", close_menu),
             widget::button("Next Level", go_next_level),
@@ -42,6 +54,19 @@ fn spawn_victory_menu(mut commands: Commands) {
     ));
 }
 
+fn steps_taken(steps: usize, par: Option<usize>) -> String {
+    let solved = match steps {
+        1 => "Solved in 1 step".to_string(),
+        steps => format!("Solved in {steps} steps"),
+    };
+    match par {
+        Some(par) if steps < par => format!("{solved}, beating par by {}!", par - steps),
+        Some(par) if steps == par => format!("{solved}, right on par"),
+        Some(par) => format!("{solved}, par is {par}"),
+        None => solved,
+    }
+}
+
 fn close_menu(_: Trigger<Pointer<Click>>, mut next_menu: ResMut<NextState<Menu>>) {
     next_menu.set(Menu::None);
 }
@@ -10,7 +10,7 @@ pub(super) fn plugin(app: &mut App) {
 
     app.add_systems(
         Update,
-        enter_gameplay_screen.run_if(in_state(Screen::Loading).and(all_assets_loaded)),
+        enter_next_screen.run_if(in_state(Screen::Loading).and(all_assets_loaded)),
     );
 }
 
@@ -22,8 +22,17 @@ fn spawn_loading_screen(mut commands: Commands) {
     ));
 }
 
-fn enter_gameplay_screen(mut next_screen: ResMut<NextState<Screen>>) {
-    next_screen.set(Screen::Gameplay);
+/// The screen to show once loading is done, gameplay unless set.
+#[derive(Resource)]
+pub struct AfterLoading(pub Screen);
+
+fn enter_next_screen(
+    mut commands: Commands,
+    after: Option<Res<AfterLoading>>,
+    mut next_screen: ResMut<NextState<Screen>>,
+) {
+    next_screen.set(after.map_or(Screen::Gameplay, |after| after.0));
+    commands.remove_resource::<AfterLoading>();
 }
 
 fn all_assets_loaded(resource_handles: Res<ResourceHandles>) -> bool {
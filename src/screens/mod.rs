@@ -2,17 +2,23 @@
 
 mod gameplay;
 mod loading;
+mod pack_select;
+mod sandbox;
 mod splash;
 mod title;
 
 use bevy::prelude::*;
 
+pub use loading::AfterLoading;
+
 pub(super) fn plugin(app: &mut App) {
     app.init_state::<Screen>();
 
     app.add_plugins((
         gameplay::plugin,
         loading::plugin,
+        pack_select::plugin,
+        sandbox::plugin,
         splash::plugin,
         title::plugin,
     ));
@@ -26,5 +32,7 @@ pub enum Screen {
     Splash,
     Title,
     Loading,
+    PackSelect,
     Gameplay,
+    Sandbox,
 }
@@ -0,0 +1,88 @@
+//! The screen where the player picks a level pack to play.
+
+use bevy::{input::common_conditions::input_just_pressed, prelude::*};
+
+use crate::{
+    game::{interface::ReachedLevel, level::Level, pack::LevelRegistry},
+    screens::Screen,
+    theme::widget::{self, ButtonSize},
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(OnEnter(Screen::PackSelect), spawn_pack_select);
+    app.add_systems(
+        Update,
+        go_back.run_if(in_state(Screen::PackSelect).and(input_just_pressed(KeyCode::Escape))),
+    );
+}
+
+fn spawn_pack_select(
+    mut commands: Commands,
+    registry: Res<LevelRegistry>,
+    reached: Res<ReachedLevel>,
+) {
+    let packs: Vec<_> = registry
+        .packs
+        .iter()
+        .enumerate()
+        .map(|(pack, contents)| {
+            let furthest = reached.get(&contents.title);
+            let progress = format!(
+                "by {}, level {} of {}",
+                contents.author,
+                furthest + 1,
+                contents.levels.len()
+            );
+            (
+                widget::button_custom(
+                    contents.title.clone(),
+                    play_pack(pack, furthest),
+                    None,
+                    Some(ButtonSize {
+                        width: 500.0,
+                        ..default()
+                    }),
+                ),
+                widget::label(progress),
+            )
+        })
+        .collect();
+    commands
+        .spawn((
+            widget::ui_root("Pack Select"),
+            StateScoped(Screen::PackSelect),
+            children![widget::header("Level Packs")],
+        ))
+        .with_children(|parent| {
+            if packs.is_empty() {
+                parent.spawn(widget::label("No level packs found"));
+            }
+            for (button, progress) in packs {
+                parent.spawn(button);
+                parent.spawn(progress);
+            }
+            parent.spawn(widget::button("Back", leave_pack_select));
+        });
+}
+
+/// Picks the pack back up at the furthest level the player has reached in it.
+fn play_pack(
+    pack: usize,
+    furthest: usize,
+) -> impl FnMut(Trigger<Pointer<Click>>, ResMut<NextState<Level>>, ResMut<NextState<Screen>>) {
+    move |_, mut level, mut screen| {
+        level.set(Level {
+            pack,
+            index: furthest,
+        });
+        screen.set(Screen::Gameplay);
+    }
+}
+
+fn leave_pack_select(_: Trigger<Pointer<Click>>, mut next_screen: ResMut<NextState<Screen>>) {
+    next_screen.set(Screen::Title);
+}
+
+fn go_back(mut next_screen: ResMut<NextState<Screen>>) {
+    next_screen.set(Screen::Title);
+}
@@ -0,0 +1,492 @@
+//! A sandbox board for Life-like rulestrings such as `B3/S23`.
+//!
+//! The rulestring is spelled out as ordinary rules for two colors, so the rule
+//! editor shows what it means and the usual step, play and reset buttons run it.
+
+use bevy::{
+    input::{
+        ButtonState,
+        common_conditions::input_just_pressed,
+        keyboard::{Key, KeyboardInput},
+    },
+    prelude::*,
+    ui::Val::*,
+};
+use rand::Rng;
+
+use crate::{
+    game::{
+        interface::{RulesWidget, settled_label, timeline},
+        level::{LevelEntity, MAX_GRID_SIZE, Puzzle},
+        logic::{
+            DisableControls, GridIterations, IterationState, MAX_GENERATIONS, PlayerRules,
+            reset_simulation, step_through, toggle_simulation,
+        },
+        objective::Objective,
+        rulestring::Rulestring,
+        simulation::{Boundary, Grid, Layout, Radius, Schedule, Tile, Topology},
+        undo::{RuleHistory, ctrl_held, undo_buttons},
+    },
+    screens::Screen,
+    theme::{
+        palette::{HEADER_TEXT, LABEL_TEXT},
+        widget::{self, BUTTON_COLORS_ALT, BUTTON_SIZE_ALT, ButtonSize},
+    },
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<RulestringInput>();
+    app.init_resource::<SandboxSize>();
+    app.add_systems(
+        OnEnter(Screen::Sandbox),
+        (spawn_sandbox, spawn_sandbox_ui).chain(),
+    );
+    app.add_systems(
+        Update,
+        (
+            type_rulestring,
+            update_rulestring_text.run_if(resource_changed::<RulestringInput>),
+            update_size_text.run_if(resource_changed::<SandboxSize>),
+            leave_sandbox.run_if(input_just_pressed(KeyCode::Escape)),
+        )
+            .run_if(in_state(Screen::Sandbox)),
+    );
+    app.add_observer(toggle_cell);
+}
+
+const DEAD: Tile = Tile::Blue;
+const ALIVE: Tile = Tile::Yellow;
+const LAYOUT: Layout = Layout {
+    width: MAX_GRID_SIZE,
+    height: MAX_GRID_SIZE,
+    boundary: Boundary::Wrap,
+    topology: Topology::Square,
+    radius: Radius::One,
+    schedule: Schedule::Sync,
+    wall: Tile::Empty,
+    hole: Tile::Empty,
+};
+/// The sides of the square boards the sandbox can be set to, past the size
+/// of any level.
+const SIZES: [usize; 5] = [MAX_GRID_SIZE, 32, 64, 128, 256];
+/// Share of live cells on a random board.
+const DENSITY: f64 = 0.3;
+const PRESETS: [(&str, &str); 4] = [
+    ("Life", "B3/S23"),
+    ("HighLife", "B36/S23"),
+    ("Seeds", "B2/S"),
+    ("Day & Night", "B3678/S34678"),
+];
+
+/// The rulestring being typed, and the last one that was applied.
+#[derive(Resource)]
+struct RulestringInput {
+    text: String,
+    rulestring: Rulestring,
+    error: Option<String>,
+}
+
+impl Default for RulestringInput {
+    fn default() -> Self {
+        let text = PRESETS[0].1.to_string();
+        Self {
+            rulestring: text.parse().unwrap(),
+            text,
+            error: None,
+        }
+    }
+}
+
+/// The side of the sandbox board, one of [`SIZES`].
+#[derive(Resource)]
+struct SandboxSize(usize);
+
+impl Default for SandboxSize {
+    fn default() -> Self {
+        Self(SIZES[0])
+    }
+}
+
+impl SandboxSize {
+    fn layout(&self) -> Layout {
+        Layout {
+            width: self.0,
+            height: self.0,
+            ..LAYOUT
+        }
+    }
+}
+
+#[derive(Component)]
+struct RulestringText;
+#[derive(Component)]
+struct RulestringError;
+#[derive(Component)]
+struct SizeText;
+
+fn spawn_sandbox(
+    mut commands: Commands,
+    input: Res<RulestringInput>,
+    size: Res<SandboxSize>,
+    mut grid_iter: ResMut<GridIterations>,
+    mut state: ResMut<NextState<IterationState>>,
+) {
+    let parent = commands
+        .spawn((
+            Name::new("Sandbox"),
+            Transform::default(),
+            Visibility::default(),
+            StateScoped(Screen::Sandbox),
+        ))
+        .id();
+    commands.insert_resource(LevelEntity(parent));
+
+    grid_iter.grid = vec![random_grid(size.layout())];
+    // Without a goal nothing can be won and the tiles show no faces.
+    grid_iter.goal.clear();
+    grid_iter.objective = Objective::default();
+    grid_iter.layout = size.layout();
+    grid_iter.max = MAX_GENERATIONS;
+    grid_iter.seed = rand::random();
+    commands.insert_resource(player_rules(input.rulestring));
+    commands.insert_resource(RuleHistory::default());
+    state.set(IterationState::Reset);
+}
+
+fn player_rules(rulestring: Rulestring) -> PlayerRules {
+    let mut rules = rulestring.rules(DEAD, ALIVE);
+    // Resetting a rule brings back the one the rulestring gave.
+    for list in rules.values_mut() {
+        for (index, rule) in list.iter_mut().enumerate() {
+            rule.preset = Some(index);
+        }
+    }
+    PlayerRules {
+        max_rules: rules.values().map(Vec::len).max().unwrap_or(1).max(1),
+        defaults: rules.clone(),
+        rules,
+        color_pool: vec![Some(DEAD), Some(ALIVE), None],
+    }
+}
+
+fn random_grid(layout: Layout) -> Grid {
+    let mut rng = rand::thread_rng();
+    (0..layout.len())
+        .map(|_| if rng.gen_bool(DENSITY) { ALIVE } else { DEAD } as u8)
+        .collect()
+}
+
+fn spawn_sandbox_ui(mut commands: Commands, input: Res<RulestringInput>, size: Res<SandboxSize>) {
+    commands.spawn((
+        widget::ui_row("Sandbox UI"),
+        GlobalZIndex(1),
+        StateScoped(Screen::Sandbox),
+        children![
+            (
+                widget::ui_split("Left Sidebar", AlignItems::FlexEnd, JustifyContent::Center),
+                children![
+                    (
+                        Node {
+                            width: Px(430.0),
+                            height: Percent(100.0),
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::FlexStart,
+                            justify_content: JustifyContent::Center,
+                            row_gap: Px(15.0),
+                            ..default()
+                        },
+                        RulesWidget
+                    ),
+                    (
+                        Node {
+                            display: Display::Flex,
+                            flex_direction: FlexDirection::Row,
+                            column_gap: Px(8.0),
+                            ..default()
+                        },
+                        children![
+                            widget::button_custom(
+                                "",
+                                toggle_simulation,
+                                Some(BUTTON_COLORS_ALT),
+                                Some(ButtonSize {
+                                    width: 120.0,
+                                    height: BUTTON_SIZE_ALT.height
+                                })
+                            ),
+                            widget::button_custom(
+                                "󰑙",
+                                reset_simulation,
+                                None,
+                                Some(BUTTON_SIZE_ALT)
+                            ),
+                            widget::button_custom("", step_through, None, Some(BUTTON_SIZE_ALT)),
+                        ],
+                    ),
+                    undo_buttons(),
+                    timeline(),
+                    settled_label(),
+                ],
+            ),
+            (
+                widget::ui_split(
+                    "Right Sidebar",
+                    AlignItems::FlexStart,
+                    JustifyContent::Center,
+                ),
+                children![(
+                    Node {
+                        width: Px(420.0),
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        row_gap: Px(15.0),
+                        ..default()
+                    },
+                    children![
+                        widget::label("Type a rulestring, Enter applies it"),
+                        (
+                            Name::new("Rulestring"),
+                            Text(format!("{}_", input.text)),
+                            TextFont::from_font_size(48.0),
+                            TextColor(HEADER_TEXT),
+                            RulestringText,
+                        ),
+                        (
+                            Name::new("Rulestring Error"),
+                            Text::default(),
+                            TextFont::from_font_size(18.0),
+                            TextColor(LABEL_TEXT),
+                            RulestringError,
+                        ),
+                        preset_button(PRESETS[0]),
+                        preset_button(PRESETS[1]),
+                        preset_button(PRESETS[2]),
+                        preset_button(PRESETS[3]),
+                        (
+                            Node {
+                                display: Display::Flex,
+                                flex_direction: FlexDirection::Row,
+                                column_gap: Px(8.0),
+                                ..default()
+                            },
+                            children![
+                                widget::button_custom(
+                                    "Random",
+                                    randomize_board,
+                                    None,
+                                    Some(ButtonSize {
+                                        width: 146.0,
+                                        height: BUTTON_SIZE_ALT.height
+                                    })
+                                ),
+                                widget::button_custom(
+                                    "Empty",
+                                    empty_board,
+                                    None,
+                                    Some(ButtonSize {
+                                        width: 146.0,
+                                        height: BUTTON_SIZE_ALT.height
+                                    })
+                                ),
+                            ],
+                        ),
+                        (
+                            Node {
+                                display: Display::Flex,
+                                flex_direction: FlexDirection::Row,
+                                align_items: AlignItems::Center,
+                                column_gap: Px(8.0),
+                                ..default()
+                            },
+                            children![
+                                widget::button_custom(
+                                    "-",
+                                    shrink_board,
+                                    None,
+                                    Some(BUTTON_SIZE_ALT)
+                                ),
+                                (
+                                    Name::new("Board Size"),
+                                    Node {
+                                        width: Px(120.0),
+                                        justify_content: JustifyContent::Center,
+                                        ..default()
+                                    },
+                                    Text(size_text(&size)),
+                                    TextFont::from_font_size(24.0),
+                                    TextColor(HEADER_TEXT),
+                                    TextLayout::new_with_justify(JustifyText::Center),
+                                    SizeText,
+                                ),
+                                widget::button_custom("+", grow_board, None, Some(BUTTON_SIZE_ALT)),
+                            ],
+                        ),
+                        widget::button("Back", go_to_title),
+                    ],
+                )],
+            ),
+        ],
+    ));
+}
+
+fn preset_button((name, rulestring): (&'static str, &'static str)) -> impl Bundle {
+    widget::button(
+        format!("{name} {rulestring}"),
+        move |_: Trigger<Pointer<Click>>,
+              mut commands: Commands,
+              mut input: ResMut<RulestringInput>,
+              mut state: ResMut<NextState<IterationState>>| {
+            input.text = rulestring.to_string();
+            apply_rulestring(&mut commands, &mut input, &mut state);
+        },
+    )
+}
+
+/// Runs the typed rulestring from the starting board, or shows why it can't.
+fn apply_rulestring(
+    commands: &mut Commands,
+    input: &mut RulestringInput,
+    state: &mut NextState<IterationState>,
+) {
+    match input.text.parse::<Rulestring>() {
+        Ok(rulestring) => {
+            input.text = rulestring.to_string();
+            input.rulestring = rulestring;
+            input.error = None;
+            commands.insert_resource(player_rules(rulestring));
+            commands.insert_resource(RuleHistory::default());
+            state.set(IterationState::Reset);
+        }
+        Err(error) => input.error = Some(error),
+    }
+}
+
+fn type_rulestring(
+    mut commands: Commands,
+    mut keys: EventReader<KeyboardInput>,
+    held: Res<ButtonInput<KeyCode>>,
+    mut input: ResMut<RulestringInput>,
+    mut state: ResMut<NextState<IterationState>>,
+) {
+    for key in keys.read().filter(|key| key.state == ButtonState::Pressed) {
+        match &key.logical_key {
+            // Ctrl+Z and the like belong to undo.
+            Key::Character(_) if ctrl_held(&held) => {}
+            Key::Character(text) => input.text.extend(
+                text.chars()
+                    .filter(|c| c.is_ascii_alphanumeric() || *c == '/'),
+            ),
+            Key::Backspace => {
+                input.text.pop();
+            }
+            Key::Enter => apply_rulestring(&mut commands, &mut input, &mut state),
+            _ => {}
+        }
+    }
+}
+
+fn update_rulestring_text(
+    input: Res<RulestringInput>,
+    mut text: Single<&mut Text, (With<RulestringText>, Without<RulestringError>)>,
+    mut error: Single<&mut Text, (With<RulestringError>, Without<RulestringText>)>,
+) {
+    text.0 = format!("{}_", input.text);
+    error.0 = input.error.clone().unwrap_or_default();
+}
+
+fn size_text(size: &SandboxSize) -> String {
+    format!("{0}x{0}", size.0)
+}
+
+fn update_size_text(size: Res<SandboxSize>, mut text: Single<&mut Text, With<SizeText>>) {
+    text.0 = size_text(&size);
+}
+
+fn shrink_board(
+    _: Trigger<Pointer<Click>>,
+    size: ResMut<SandboxSize>,
+    grid_iter: ResMut<GridIterations>,
+    state: ResMut<NextState<IterationState>>,
+) {
+    resize_board(-1, size, grid_iter, state);
+}
+
+fn grow_board(
+    _: Trigger<Pointer<Click>>,
+    size: ResMut<SandboxSize>,
+    grid_iter: ResMut<GridIterations>,
+    state: ResMut<NextState<IterationState>>,
+) {
+    resize_board(1, size, grid_iter, state);
+}
+
+/// Moves the board `by` places through [`SIZES`] and starts over on a random
+/// board of the new size.
+fn resize_board(
+    by: isize,
+    mut size: ResMut<SandboxSize>,
+    mut grid_iter: ResMut<GridIterations>,
+    mut state: ResMut<NextState<IterationState>>,
+) {
+    let index = SIZES.iter().position(|&side| side == size.0).unwrap_or(0);
+    let Some(&side) = index
+        .checked_add_signed(by)
+        .and_then(|index| SIZES.get(index))
+    else {
+        return;
+    };
+    size.0 = side;
+    grid_iter.layout = size.layout();
+    grid_iter.grid = vec![random_grid(size.layout())];
+    state.set(IterationState::Reset);
+}
+
+/// Clicking a tile flips it between dead and alive, until the board is run.
+fn toggle_cell(
+    trigger: Trigger<Pointer<Click>>,
+    mut commands: Commands,
+    screen: Res<State<Screen>>,
+    locked: Option<Res<DisableControls>>,
+    cells: Query<(&Puzzle, &Tile)>,
+    mut grid_iter: ResMut<GridIterations>,
+) {
+    if *screen.get() != Screen::Sandbox
+        || locked.is_some()
+        || trigger.event.button != PointerButton::Primary
+    {
+        return;
+    }
+    let Ok((cell, &tile)) = cells.get(trigger.target()) else {
+        return;
+    };
+    let tile = if tile == ALIVE { DEAD } else { ALIVE };
+    grid_iter.grid.last_mut().unwrap()[cell.0] = tile as u8;
+    commands.entity(trigger.target()).insert(tile);
+}
+
+fn randomize_board(
+    _: Trigger<Pointer<Click>>,
+    mut grid_iter: ResMut<GridIterations>,
+    mut state: ResMut<NextState<IterationState>>,
+) {
+    let layout = grid_iter.layout;
+    grid_iter.grid = vec![random_grid(layout)];
+    state.set(IterationState::Reset);
+}
+
+fn empty_board(
+    _: Trigger<Pointer<Click>>,
+    mut grid_iter: ResMut<GridIterations>,
+    mut state: ResMut<NextState<IterationState>>,
+) {
+    grid_iter.grid = vec![vec![DEAD as u8; grid_iter.layout.len()]];
+    state.set(IterationState::Reset);
+}
+
+fn go_to_title(_: Trigger<Pointer<Click>>, mut next_screen: ResMut<NextState<Screen>>) {
+    next_screen.set(Screen::Title);
+}
+
+fn leave_sandbox(mut next_screen: ResMut<NextState<Screen>>) {
+    next_screen.set(Screen::Title);
+}
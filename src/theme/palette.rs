@@ -29,3 +29,5 @@ pub const SOCKET: Color = Color::srgb(0.063, 0.071, 0.110);
 pub const ENABLED: Color = Color::srgb(0.616, 0.902, 0.310);
 pub const DISABLED: Color = Color::srgb(0.965, 0.910, 0.878);
 pub const INVERTED: Color = Color::srgb(0.925, 0.153, 0.247);
+/// Frames and text of rule controls the level has locked.
+pub const LOCKED: Color = Color::srgb(0.341, 0.325, 0.392);
@@ -10,9 +10,15 @@ use bevy::{
 
 use crate::{
     game::{
-        interface::{ColorPickerButton, InvertToggleButton, MaskToggleButton, ResetRuleButton},
-        level::Tile,
-        logic::Rule,
+        interface::{
+            AddRuleButton, ChanceButton, ColorPickerButton, CombinatorButton, CountButton,
+            InvertToggleButton, MaskToggleButton, MoveRuleButton, RemoveRuleButton,
+            ResetRuleButton,
+        },
+        simulation::{
+            Combinator, Count, Layout, Locks, MAX_NEIGHBOURS, NEIGHBOUR_OFFSETS, Radius, Rule,
+            Tile, Topology,
+        },
     },
     theme::{interaction::InteractionPalette, palette::*},
 };
@@ -320,7 +326,8 @@ pub fn ui_row(name: impl Into<Cow<'static, str>>) -> impl Bundle {
 pub struct ColorPickerEvent {
     pub color: Color,
 }
-pub fn color_picker(tile: Option<Tile>, action: impl Bundle) -> impl Bundle {
+/// Locked pickers sit in a dark frame with a padlock and don't light up on hover.
+pub fn color_picker(tile: Option<Tile>, locked: bool, action: impl Bundle) -> impl Bundle {
     let mut offset = Vec2::ZERO;
     if let Some(tile) = tile {
         offset.x = tile as u8 as f32;
@@ -333,6 +340,19 @@ pub fn color_picker(tile: Option<Tile>, action: impl Bundle) -> impl Bundle {
     } else {
         SOCKET
     };
+    let palette = if locked {
+        InteractionPalette {
+            none: LOCKED,
+            hovered: LOCKED,
+            pressed: LOCKED,
+        }
+    } else {
+        InteractionPalette {
+            none: BUTTON_PRESSED_BACKGROUND,
+            hovered: BUTTON_BACKGROUND,
+            pressed: BUTTON_BACKGROUND,
+        }
+    };
     (
         Name::new("Color Picker"),
         Node::default(),
@@ -341,22 +361,26 @@ pub fn color_picker(tile: Option<Tile>, action: impl Bundle) -> impl Bundle {
                 .spawn((
                     Name::new("Picker Inner"),
                     action,
-                    BackgroundColor(BUTTON_PRESSED_BACKGROUND),
-                    InteractionPalette {
-                        none: BUTTON_PRESSED_BACKGROUND,
-                        hovered: BUTTON_BACKGROUND,
-                        pressed: BUTTON_BACKGROUND,
-                    },
+                    BackgroundColor(palette.none),
+                    palette,
                     children![(
                         Name::new("Picker Color"),
                         Node {
                             width: Val::Px(50.0),
                             height: Val::Px(50.0),
+                            align_items: AlignItems::Center,
+                            justify_content: JustifyContent::Center,
                             ..default()
                         },
                         BackgroundColor(background_color),
                         BorderRadius::all(Val::Px(10.0)),
                         Pickable::IGNORE,
+                        children![(
+                            Text::new(if locked { "󰌾" } else { "" }),
+                            TextColor(LOCKED),
+                            TextFont::from_font_size(24.0),
+                            Pickable::IGNORE,
+                        )],
                     )],
                 ))
                 .insert((
@@ -372,10 +396,12 @@ pub fn color_picker(tile: Option<Tile>, action: impl Bundle) -> impl Bundle {
         })),
     )
 }
+/// Locked pickers are framed in [`LOCKED`] rather than the usual light frame.
 pub fn direction_picker(
     value: bool,
     invert: bool,
     is_invert_toggle: bool,
+    locked: bool,
     action: impl Bundle,
 ) -> impl Bundle {
     let color = if invert {
@@ -422,7 +448,7 @@ pub fn direction_picker(
                         justify_content: JustifyContent::Center,
                         ..default()
                     },
-                    BackgroundColor(BUTTON_TEXT_ALT),
+                    BackgroundColor(if locked { LOCKED } else { BUTTON_TEXT_ALT }),
                     BorderRadius::all(Val::Px(match is_invert_toggle {
                         true => 10.0,
                         false => 0.0,
@@ -431,7 +457,9 @@ pub fn direction_picker(
         })),
     )
 }
-pub fn rule_ui(tile: Tile, rule: Rule) -> impl Bundle {
+/// One rule of a color. `index` is its place among the `count` rules of that
+/// color, the list buttons only show up once there is more than one.
+pub fn rule_ui(tile: Tile, index: usize, count: usize, rule: Rule, layout: Layout) -> impl Bundle {
     (
         Name::new("Rule UI"),
         Node {
@@ -459,50 +487,46 @@ pub fn rule_ui(tile: Tile, rule: Rule) -> impl Bundle {
                 BackgroundColor(tile.color()),
                 BorderRadius::all(Val::Px(10.0)),
                 children![
-                    color_picker(Some(tile), (Visibility::default(), Transform::default())),
-                    (
-                        Node {
-                            display: Display::Grid,
-                            margin: UiRect {
-                                left: Val::Px(8.0),
-                                right: Val::Px(8.0),
-                                ..default()
-                            },
-                            row_gap: Px(5.0),
-                            column_gap: Px(5.0),
-                            grid_template_columns: RepeatedGridTrack::px(3, 16.0),
-                            ..default()
-                        },
-                        Children::spawn(SpawnWith(move |parent: &mut ChildSpawner| {
-                            for i in [5, 6, 7, 3, 4, 0, 1, 2] {
-                                if i == 4 {
-                                    parent.spawn(direction_picker(
-                                        rule.invert,
-                                        false,
-                                        true,
-                                        InvertToggleButton { tile },
-                                    ));
-                                }
-                                parent.spawn(direction_picker(
-                                    rule.mask[i],
-                                    rule.invert,
-                                    false,
-                                    MaskToggleButton { tile, index: i },
-                                ));
-                            }
-                        })),
+                    color_picker(
+                        Some(tile),
+                        false,
+                        (Visibility::default(), Transform::default())
+                    ),
+                    mask_editor(tile, index, rule.mask, rule.invert, rule.locked, layout),
+                    combinator_button(tile, index, rule.combinator, rule.locked.rule),
+                    count_editor(
+                        tile,
+                        index,
+                        rule.count,
+                        layout.neighbour_count(),
+                        rule.locked.rule
                     ),
                     color_picker(
                         rule.tiles[0],
+                        rule.locked.tiles(),
                         (
                             Button,
                             ColorPickerButton {
                                 tile,
+                                rule: index,
                                 index: 0,
                                 color: rule.tiles[0],
                             },
                         )
                     ),
+                    color_picker(
+                        rule.tiles[1],
+                        rule.locked.tiles(),
+                        (
+                            Button,
+                            ColorPickerButton {
+                                tile,
+                                rule: index,
+                                index: 1,
+                                color: rule.tiles[1],
+                            },
+                        )
+                    ),
                 ]
             ),
             (
@@ -528,12 +552,15 @@ pub fn rule_ui(tile: Tile, rule: Rule) -> impl Bundle {
                         TextColor(DISABLED),
                         TextFont::from_font_size(24.0),
                     ),
+                    chance_button(tile, index, rule.chance, rule.locked.rule),
                     color_picker(
                         rule.result,
+                        rule.locked.result(),
                         (
                             Button,
                             ColorPickerButton {
                                 tile,
+                                rule: index,
                                 index: 2,
                                 color: rule.result
                             }
@@ -541,7 +568,13 @@ pub fn rule_ui(tile: Tile, rule: Rule) -> impl Bundle {
                     ),
                     (
                         Button,
-                        ResetRuleButton { tile },
+                        ResetRuleButton { tile, rule: index },
+                        // A locked rule is already what the level wants it to be.
+                        if rule.locked.rule {
+                            Visibility::Hidden
+                        } else {
+                            Visibility::Inherited
+                        },
                         Node {
                             width: Val::Px(30.0),
                             height: Val::Px(30.0),
@@ -558,6 +591,259 @@ pub fn rule_ui(tile: Tile, rule: Rule) -> impl Bundle {
                     ),
                 ]
             ),
+            rule_list_controls(tile, index, count, rule.locked.any()),
+        ],
+    )
+}
+
+/// Cycles how the rule's trigger colors combine: any of them, all of them or none.
+fn combinator_button(tile: Tile, rule: usize, combinator: Combinator, locked: bool) -> impl Bundle {
+    let label = match combinator {
+        Combinator::AnyOf => "any",
+        Combinator::AllOf => "all",
+        Combinator::NoneOf => "none",
+    };
+    (
+        Name::new("Combinator Button"),
+        Button,
+        CombinatorButton { tile, rule },
+        Node {
+            width: Val::Px(40.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        children![(
+            Text::new(label),
+            TextColor(if locked { LOCKED } else { DISABLED }),
+            TextFont::from_font_size(20.0),
+            Pickable::IGNORE,
+        )],
+    )
+}
+
+/// Cycles how often the rule goes through when it fires.
+fn chance_button(tile: Tile, rule: usize, chance: u8, locked: bool) -> impl Bundle {
+    (
+        Name::new("Chance Button"),
+        Button,
+        ChanceButton { tile, rule },
+        Node {
+            width: Val::Px(48.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        children![(
+            Text::new(format!("{chance}%")),
+            TextColor(if locked { LOCKED } else { DISABLED }),
+            TextFont::from_font_size(20.0),
+            Pickable::IGNORE,
+        )],
+    )
+}
+
+/// How many masked neighbours the rule asks for, as a "min-max" pair of numbers
+/// that step up when clicked.
+/// Upper bounds past the `limit` of neighbours a cell has read as the limit.
+fn count_editor(tile: Tile, rule: usize, count: Count, limit: u8, locked: bool) -> impl Bundle {
+    let color = if locked { LOCKED } else { DISABLED };
+    let number = move |value: u8, max: bool| {
+        (
+            Button,
+            CountButton { tile, rule, max },
+            Node {
+                width: Val::Px(16.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            children![(
+                Text::new(value.to_string()),
+                TextColor(color),
+                TextFont::from_font_size(20.0),
+                Pickable::IGNORE,
+            )],
+        )
+    };
+    (
+        Name::new("Count Editor"),
+        Node {
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        children![
+            number(count.min, false),
+            (
+                Text::new("-"),
+                TextColor(color),
+                TextFont::from_font_size(20.0),
+                Pickable::IGNORE,
+            ),
+            number(count.max.min(limit), true),
         ],
     )
 }
+
+/// Buttons to move a rule up or down its color's list, or drop it. Rules with
+/// anything `pinned` by the level have none.
+fn rule_list_controls(tile: Tile, rule: usize, count: usize, pinned: bool) -> impl Bundle {
+    (
+        Node {
+            display: if count > 1 && !pinned {
+                Display::Flex
+            } else {
+                Display::None
+            },
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        Children::spawn(SpawnWith(move |parent: &mut ChildSpawner| {
+            if rule > 0 {
+                parent.spawn(rule_list_button(
+                    "",
+                    MoveRuleButton {
+                        tile,
+                        rule,
+                        up: true,
+                    },
+                ));
+            }
+            parent.spawn(rule_list_button("", RemoveRuleButton { tile, rule }));
+            if rule + 1 < count {
+                parent.spawn(rule_list_button(
+                    "",
+                    MoveRuleButton {
+                        tile,
+                        rule,
+                        up: false,
+                    },
+                ));
+            }
+        })),
+    )
+}
+
+/// Adds another rule to the end of a color's list.
+pub fn add_rule_button(tile: Tile) -> impl Bundle {
+    (
+        Name::new("Add Rule"),
+        Node {
+            width: Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Pickable::IGNORE,
+        children![(
+            Node {
+                padding: UiRect::horizontal(Val::Px(16.0)),
+                ..default()
+            },
+            BackgroundColor(tile.color()),
+            BorderRadius::all(Val::Px(10.0)),
+            children![rule_list_button("", AddRuleButton { tile })],
+        )],
+    )
+}
+
+fn rule_list_button(icon: &'static str, action: impl Component) -> impl Bundle {
+    (
+        Button,
+        action,
+        Node {
+            width: Val::Px(24.0),
+            height: Val::Px(24.0),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        children![(
+            Text::new(icon),
+            TextColor(DISABLED),
+            TextFont::from_font_size(20.0),
+            Pickable::IGNORE,
+        )],
+    )
+}
+
+/// The clickable neighbourhood of a rule, laid out like the cells around a tile
+/// with the invert toggle in the middle.
+fn mask_editor(
+    tile: Tile,
+    rule: usize,
+    mask: [bool; MAX_NEIGHBOURS],
+    invert: bool,
+    locked: Locks,
+    layout: Layout,
+) -> impl Bundle {
+    let picker = move |i: usize| {
+        let button = MaskToggleButton {
+            tile,
+            rule,
+            index: i,
+        };
+        direction_picker(mask[i], invert, false, locked.mask(), button)
+    };
+    let invert_toggle = move || {
+        let button = InvertToggleButton { tile, rule };
+        direction_picker(invert, false, true, locked.invert(), button)
+    };
+    let margin = UiRect {
+        left: Val::Px(8.0),
+        right: Val::Px(8.0),
+        ..default()
+    };
+    // Square boards with a radius of two get a 5x5 picker, its outer ring
+    // being the mask entries past the touching cells.
+    let reach = match layout.radius {
+        Radius::One => 1,
+        Radius::Two => 2,
+    };
+    let gap = if reach == 2 { 2.0 } else { 5.0 };
+    let node = match layout.topology {
+        Topology::Square => Node {
+            display: Display::Grid,
+            margin,
+            row_gap: Px(gap),
+            column_gap: Px(gap),
+            grid_template_columns: RepeatedGridTrack::px(2 * reach + 1, 16.0),
+            ..default()
+        },
+        // Centered rows, so the outer ones sit half a cell in like hex neighbours do.
+        Topology::Hex => Node {
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            margin,
+            row_gap: Px(5.0),
+            ..default()
+        },
+    };
+    (
+        node,
+        Children::spawn(SpawnWith(move |parent: &mut ChildSpawner| {
+            match layout.topology {
+                Topology::Square => {
+                    let reach = reach as isize;
+                    for dy in (-reach..=reach).rev() {
+                        for dx in -reach..=reach {
+                            match NEIGHBOUR_OFFSETS
+                                .iter()
+                                .position(|&offset| offset == (dx, dy))
+                            {
+                                Some(i) => parent.spawn(picker(i)),
+                                None => parent.spawn(invert_toggle()),
+                            };
+                        }
+                    }
+                }
+                Topology::Hex => {
+                    let row = || Node {
+                        column_gap: Px(5.0),
+                        ..default()
+                    };
+                    parent.spawn((row(), children![picker(5), picker(7)]));
+                    parent.spawn((row(), children![picker(3), invert_toggle(), picker(4)]));
+                    parent.spawn((row(), children![picker(0), picker(2)]));
+                }
+            }
+        })),
+    )
+}